@@ -1,5 +1,7 @@
 #!/usr/bin/env python3
 import argparse as ap
+import json
+import numpy as np
 import subprocess as sp
 from pyscf import scf, gto
 
@@ -30,100 +32,50 @@ def parse_ctab(text):
                   comment = comment,
                   atoms = atoms)
 
-def collect_atoms(mol):
+def collect_scene(mol, mf):
     atoms = []
+    bases = []
 
     for i in range(mol.natm):
-        coords = list(mol.atom_coord(i))
-        atom = (i, coords)
-        atoms.append(atom)
-
-    return atoms
-
-def collect_basis_set(mol):
-    basis_set = { }
+        atoms.append({ "element": mol.atom_pure_symbol(i),
+                       "position": mol.atom_coord(i).tolist(),
+                       "basis": i })
+        bases.append({ "shells": [], "normalization": "contracted" })
 
     for i in range(mol.nbas):
-        atom = mol.bas_atom(i);
-        l = mol.bas_angular(i);
-
-        if atom not in basis_set.keys():
-            basis_set[atom] = []
-
-        if l == 0:
-            expns = list(mol.bas_exp(i))
-            basis_set[atom].append((expns, []))
-
-        coefs = [c[0] for c in mol.bas_ctr_coeff(i)]
-        basis_set[atom][-1][1].append(coefs)
-
-    return basis_set
-
-def run_mo_draw(basis_set, atoms, mo_coefs):
-    def to_b_arg(basis):
-        n = 1
-        s = "["
-
-        for (expns, coefses) in basis:
-            s += "N{n} ".format(n = n)
-
-            for expn in expns:
-                s += "{x} ".format(x = expn)
-
-            l = 0
-
-            for coefs in coefses:
-                s += "L{l} ".format(l = l)
-
-                for coef in coefs:
-                    s += "{x} ".format(x = coef)
-
-                l += 1
-
-            n += 1
-
-        s = s[:-1] + "]"
-
-        return s
-
-    def to_a_arg(atom):
-        (b, pos) = atom
-        s = "[B{b} X{x} Y{y} Z{z}]".format(b = b,
-                                             x = pos[0],
-                                             y = pos[1],
-                                             z = pos[2])
-
-        return s
-
-    def to_c_arg(mo_coefs):
-        s = "["
-
-        for row in mo_coefs:
-            s += "["
-
-            for coef in row:
-                s += "{x} ".format(x = coef)
-
-            s = s[:-1] + "] "
-
-        s = s[:-1] + "]"
-
-        return s
-
-    b_args = [to_b_arg(basis) for basis in basis_set.values()]
-    a_args = [to_a_arg(atom) for atom in atoms]
-    c_arg = to_c_arg(mo_coefs)
-
-    args = ["./mo-draw/mo-draw"]
-
-    for arg in b_args: args += ["-B", arg]
-    for arg in a_args: args += ["-A", arg]
-
-    args += ["-C", c_arg]
-
-    print("Num mo coefs: {x}".format( x = len(mo_coefs[1])))
-    #print(args)
-    sp.run(args)
+        shells = bases[mol.bas_atom(i)]["shells"]
+        expns = mol.bas_exp(i).tolist()
+
+        # shells with several contractions become one shell per contraction
+        for coefs in mol.bas_ctr_coeff(i).T:
+            shells.append({ "l": int(mol.bas_angular(i)),
+                            "cartesian": bool(mol.cart),
+                            "exponents": expns,
+                            "coefficients": coefs.tolist() })
+
+    # pyscf only normalizes the x^l component of a cartesian shell, while
+    # mo-draw normalizes every component, so rescale by each ao's own norm
+    ao_norms = np.sqrt(np.diag(mol.intor("int1e_ovlp"))) if mol.cart else 1.0
+
+    # mo_coeff keeps one mo per column
+    mos = [{ "energy": float(energy),
+             "occupation": float(occ),
+             "coefficients": (coefs * ao_norms).tolist() }
+           for (energy, occ, coefs) in zip(mf.mo_energy, mf.mo_occ, mf.mo_coeff.T)]
+
+    return { "version": 1,
+             "units": "bohr",
+             "ordering": "pyscf",
+             "atoms": atoms,
+             "bases": bases,
+             "mos": mos }
+
+def run_mo_draw(scene):
+    # the scene goes over stdin, large coefficient matrices overflow argv
+    print("Num MOs: {x}".format(x = len(scene["mos"])))
+    sp.run(["./mo-draw/mo-draw", "view", "--scene", "-"],
+           input = json.dumps(scene),
+           text = True)
 
 def main():
     parser = ap.ArgumentParser(prog = "ch121-final",
@@ -133,6 +85,9 @@ def main():
     parser.add_argument("-b", "--basis",
                         help = "basis set to use",
                         default = "sto-3g")
+    parser.add_argument("-c", "--cart",
+                        help = "use cartesian instead of spherical d, f and g shells",
+                        action = "store_true")
 
     args = parser.parse_args()
     ctab = None
@@ -141,21 +96,14 @@ def main():
         ctab = parse_ctab(file.read())
 
     mol_str = "; ".join([" ".join([a.elem] + a.pos) for a in ctab.atoms])
-    mol = gto.M(atom = mol_str, basis = args.basis)
+    mol = gto.M(atom = mol_str, basis = args.basis, cart = args.cart)
     mf = scf.hf.SCF(mol)
     mf.scf()
 
-    basis_set = collect_basis_set(mol)
-    atoms = collect_atoms(mol)
-    mo_coefs = mf.mo_coeff
-
-    #print(basis_set)
-    #print(atoms)
-    #print(mo_coefs)
     print("MO (occ, energy):")
     print(list(zip(mf.mo_energy, mf.mo_occ)))
 
-    run_mo_draw(basis_set, atoms, mo_coefs)
+    run_mo_draw(collect_scene(mol, mf))
 
 if __name__ == "__main__":
     main()
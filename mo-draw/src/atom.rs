@@ -1,8 +1,12 @@
 use crate::error::Error;
 
-#[derive(Debug)]
+// one angstrom in bohr, which is what positions are kept in
+pub const ANGSTROM: f64 = 1.0 / 0.529177210903;
+
+#[derive(Debug, Clone)]
 pub struct Atom {
     pub basis_id:   usize,
+    pub atomic_num: u32,
     pub position:   [f64; 3],
 }
 
@@ -23,6 +27,6 @@ impl Atom {
             }
         }
 
-        Ok(Self { basis_id, position })
+        Ok(Self { basis_id, atomic_num: 0, position })
     }
 }
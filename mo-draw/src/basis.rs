@@ -1,26 +1,141 @@
-use ndarray::{ Array2 };
-use crate::error::Error;
+use std::{
+    collections::BTreeMap,
+    f64::consts::PI,
+};
+use clap::ValueEnum;
+use ndarray::{ Array1, Array2 };
+use serde::Deserialize;
+use crate::{
+    atom::Atom,
+    error::Error,
+    harmonics,
+};
 
-pub fn nl_index(n: i32, l: i32) -> usize {
-    const OFFSETS: [usize; 5] = [0, 1, 3, 6, 10];
-    OFFSETS[n as usize - 1] + l as usize
+// a single contracted shell as input formats list them
+#[derive(Debug, Clone)]
+pub struct Shell {
+    pub l:      i32,
+    // x^i y^j z^k components instead of solid harmonics
+    pub cart:   bool,
+    pub expns:  Vec<f64>,
+    pub coefs:  Vec<f64>,
 }
 
-pub fn lm_index(l: i32, m: i32) -> usize {
-    (2 * l + m) as usize
+impl Shell {
+    // sp shells share exponents between an s and a p shell
+    pub fn split_sp(expns:      Vec<f64>,
+                    s_coefs:    Vec<f64>,
+                    p_coefs:    Vec<f64>) -> [Self; 2] {
+        [
+            Self { l: 0, cart: false, expns: expns.clone(), coefs: s_coefs },
+            Self { l: 1, cart: false, expns, coefs: p_coefs },
+        ]
+    }
+
+    // spherical shells carry one ao for each m on [-l, l]
+    pub fn num_aos(&self) -> usize {
+        if self.cart {
+            harmonics::num_cart(self.l)
+        } else {
+            2 * self.l as usize + 1
+        }
+    }
+
+    // what a primitive is scaled by to normalize it, past whatever the
+    // angular functions in harmonics already carry
+    fn prim_norm(&self, expn: f64) -> f64 {
+        let l = self.l as f64;
+
+        if self.cart {
+            (2.0 * expn / PI).powf(0.75) * (4.0 * expn).powf(0.5 * l)
+        } else {
+            // the solid harmonics are normalized on the unit sphere, so
+            // this is just r^l e^(-a r^2) normalized over the radius
+            let gamma = harmonics::double_factorial(2 * self.l + 1) * PI.sqrt()
+                      / 2f64.powi(self.l + 1);
+
+            (2.0 * (2.0 * expn).powf(l + 1.5) / gamma).sqrt()
+        }
+    }
+
+    // contraction coefficients with every primitive's norm folded in, so
+    // the evaluators only have to sum c e^(-a r^2)
+    pub fn norm_coefs(&self) -> Vec<f64> {
+        self.expns.iter()
+                  .zip(&self.coefs)
+                  .map(|(&expn, coef)| coef * self.prim_norm(expn))
+                  .collect()
+    }
+
+    // of the whole contracted function, taking the primitives as normalized
+    pub fn contraction_norm(&self) -> f64 {
+        let mut norm = 0.0;
+
+        for (a, ca) in self.expns.iter().zip(&self.coefs) {
+            for (b, cb) in self.expns.iter().zip(&self.coefs) {
+                norm += ca * cb * (2.0 * (a * b).sqrt() / (a + b)).powf(self.l as f64 + 1.5);
+            }
+        }
+
+        norm
+    }
+
+    // how far out any of its aos can still reach tolerance once scaled by
+    // coef, bounding every primitive by the most diffuse one
+    pub fn extent(&self, coef: f64, tolerance: f64) -> f64 {
+        let expn = self.expns.iter()
+                             .copied()
+                             .fold(f64::INFINITY, f64::min);
+        let sum = self.norm_coefs()
+                      .iter()
+                      .map(|c| c.abs())
+                      .sum::<f64>();
+
+        gaussian_extent(coef * sum, self.l, expn, tolerance)
+    }
+}
+
+// the radius past which c r^l e^(-a r^2) stays under tolerance; neither the
+// solid harmonics nor the cartesian components outgrow r^l by much
+pub fn gaussian_extent(coef: f64, l: i32, expn: f64, tolerance: f64) -> f64 {
+    let l = l as f64;
+    // the far side of the peak, where r^l gives way to the exponential
+    let peak = 0.5 * l / expn;
+    let log_coef = (coef.abs() / tolerance).ln();
+    let mut r2 = (log_coef / expn).max(peak);
+
+    // r^2 = (ln(c / tol) + l ln r) / a, which converges past the peak
+    for _ in 0..16 {
+        r2 = ((log_coef + 0.5 * l * r2.max(f64::MIN_POSITIVE).ln()) / expn).max(peak);
+    }
+
+    r2.sqrt()
+}
+
+// what an input's contraction coefficients are written against; either way
+// they multiply normalized primitives
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShellNorm {
+    // and the contraction is taken as written
+    #[default]
+    Primitive,
+    // and the whole contraction is rescaled to unit norm afterwards, as
+    // molden does
+    Contracted,
 }
 
+// shells in the order their aos appear in the mo coefficients
 #[derive(Debug)]
 pub struct Basis {
-    pub order:  usize,
-    pub expns:  Array2<f64>,
-    pub coefs:  Array2<f64>,
+    pub shells: Vec<Shell>,
 }
 
 impl Basis {
     // there are so many better ways to do this that I didn't have time for
     pub fn from_arg(arg: &str) -> Result<Self, Error> {
-        let mut coefs: Vec<Vec<f64>> = Vec::new();
+        // aos still come out in hydrogen-like (n, l) order
+        let mut coefs: BTreeMap<(i32, i32), Vec<f64>> = BTreeMap::new();
         let mut expns: Vec<Vec<f64>> = Vec::new();
 
         enum State {
@@ -51,12 +166,14 @@ impl Basis {
                     State::PushExpn(n) => n,
                     State::PushCoef(n, _l) => n,
                 };
-                let i = nl_index(n, l);
 
-                if i >= coefs.len() {
-                    coefs.resize(i + 1, Vec::new());
+                if l < 0 || l >= n {
+                    return Err("invalid L in basis specification".into());
                 }
 
+                coefs.entry((n, l))
+                     .or_default();
+
                 state = State::PushCoef(n, l);
             } else if let State::PushExpn(n) = state {
                 let expn: f64 = item.parse()?;
@@ -65,35 +182,273 @@ impl Basis {
                 expns[i].push(expn);
             } else if let State::PushCoef(n, l) = state {
                 let coef: f64 = item.parse()?;
-                let i = nl_index(n, l);
 
-                coefs[i].push(coef);
+                coefs.get_mut(&(n, l))
+                     .unwrap()
+                     .push(coef);
             } else {
                 return Err("invalid basis specficiation".into());
             }
         }
 
-        fn get_order(expns: &Vec<Vec<f64>>,
-                     coefs: &Vec<Vec<f64>>) -> Result<usize, Error> {
-            let order = expns[0].len();
-            let vecs = expns[1..].into_iter()
-                                 .chain(coefs.into_iter());
+        // an n's exponents are shared by every l given under it
+        let shells = coefs.into_iter()
+                          .map(|((n, l), coefs)| Shell {
+                              l,
+                              cart: false,
+                              expns: expns[n as usize - 1].clone(),
+                              coefs,
+                          })
+                          .collect::<Vec<_>>();
+
+        Self::from_shells(&shells, ShellNorm::Primitive)
+    }
+
+    pub fn from_shells(shells: &[Shell], norm: ShellNorm) -> Result<Self, Error> {
+        if shells.is_empty() {
+            return Err("empty basis".into());
+        }
+
+        for shell in shells {
+            if shell.l < 0 || shell.l > harmonics::MAX_L {
+                return Err(format!("basis shells are limited to l <= {}", harmonics::MAX_L).into());
+            } else if shell.expns.is_empty() {
+                return Err("basis shell has no primitives".into());
+            } else if shell.expns.len() != shell.coefs.len() {
+                return Err("basis shell has mismatched exponents and coefficients".into());
+            }
+        }
+
+        let mut shells = shells.to_vec();
 
-            for vec in vecs {
-                if vec.len() != order {
-                    return Err("inconsistent basis order".into());
+        if norm == ShellNorm::Contracted {
+            for shell in &mut shells {
+                let scale = shell.contraction_norm()
+                                 .sqrt();
+
+                // bad exponents are left for validation to point out
+                if scale.is_finite() && scale > 0.0 {
+                    shell.coefs
+                         .iter_mut()
+                         .for_each(|c| *c /= scale);
                 }
             }
+        }
+
+        Ok(Self { shells })
+    }
+
+    pub fn num_aos(&self) -> usize {
+        self.shells.iter()
+                   .map(|s| s.num_aos())
+                   .sum()
+    }
+
+    // names like 1s 2s 2p, numbering each l's shells in the order they come
+    pub fn shell_labels(&self) -> Vec<String> {
+        let mut counts = [0; harmonics::MAX_L as usize + 1];
+
+        self.shells.iter()
+                   .map(|s| {
+                       let l = s.l as usize;
+                       counts[l] += 1;
+
+                       format!("{}{}", counts[l] + l, harmonics::L_LABELS[l])
+                   })
+                   .collect()
+    }
+}
+
+// cartesian components as programs spell them out
+const MOLDEN_CART_D: [&str; 6] = ["xx", "yy", "zz", "xy", "xz", "yz"];
+const MOLDEN_CART_F: [&str; 10] = [
+    "xxx", "yyy", "zzz", "xyy", "xxy", "xxz", "xzz", "yzz", "yyz", "xyz",
+];
+const MOLDEN_CART_G: [&str; 15] = [
+    "xxxx", "yyyy", "zzzz", "xxxy", "xxxz", "yyyx", "yyyz", "zzzx",
+    "zzzy", "xxyy", "xxzz", "yyzz", "xxyz", "yyxz", "zzxy",
+];
+const GAUSSIAN_CART_G: [&str; 15] = [
+    "zzzz", "yzzz", "yyzz", "yyyz", "yyyy", "xzzz", "xyzz", "xyyz",
+    "xyyy", "xxzz", "xxyz", "xxyy", "xxxz", "xxxy", "xxxx",
+];
 
-            Ok(order)
+fn spelled_pows(names: &[&str]) -> Vec<[u32; 3]> {
+    names.iter()
+         .map(|name| ['x', 'y', 'z'].map(|c| name.matches(c).count() as u32))
+         .collect()
+}
+
+// how a program lists the aos within each shell; mo-draw itself runs m over
+// [-l, l] and cartesian components as harmonics::cart_pows does
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum AoOrdering {
+    #[default]
+    Native,
+    Pyscf,
+    Molden,
+    Gaussian,
+    Orca,
+    Qchem,
+}
+
+impl AoOrdering {
+    // the m of each pure ao, in the order the program writes them
+    fn pure_order(self, l: i32) -> Vec<i32> {
+        match (self, l) {
+            (Self::Native, _) => (-l..=l).collect(),
+            // everyone but mo-draw writes p as x y z
+            (_, 1) => vec![1, -1, 0],
+            (Self::Pyscf | Self::Qchem, _) => (-l..=l).collect(),
+            // 0, +1, -1, +2, -2, ...
+            (Self::Molden | Self::Gaussian | Self::Orca, _) => {
+                (0..=l).flat_map(|m| if m == 0 { vec![0] } else { vec![m, -m] })
+                       .collect()
+            },
+        }
+    }
+
+    fn cart_order(self, l: i32) -> Vec<[u32; 3]> {
+        match (self, l) {
+            (Self::Native | Self::Pyscf, _) | (_, 0 | 1) => harmonics::cart_pows(l),
+            // orca has no cartesian shells, so it only ever shows up in
+            // molden files
+            (Self::Molden | Self::Gaussian | Self::Orca, 2) => spelled_pows(&MOLDEN_CART_D),
+            (Self::Molden | Self::Gaussian | Self::Orca, 3) => spelled_pows(&MOLDEN_CART_F),
+            (Self::Gaussian, _) => spelled_pows(&GAUSSIAN_CART_G),
+            (Self::Molden | Self::Orca, _) => spelled_pows(&MOLDEN_CART_G),
+            // z power slowest, then y, so d runs xx xy yy xz yz zz
+            (Self::Qchem, _) => {
+                let l = l as u32;
+
+                (0..=l).flat_map(|k| (0..=(l - k)).map(move |j| [l - j - k, j, k]))
+                       .collect()
+            },
+        }
+    }
+
+    // orca flips the sign of its pure functions with |m| >= 3
+    fn sign(self, m: i32) -> f64 {
+        match self {
+            Self::Orca if m.abs() >= 3 => -1.0,
+            _ => 1.0,
+        }
+    }
+
+    // for each of mo-draw's aos in a shell, the program's ao it comes from
+    // within that shell and the sign to bring it over with
+    fn shell_map(self, shell: &Shell) -> Vec<(usize, f64)> {
+        let l = shell.l;
+
+        if shell.cart {
+            let order = self.cart_order(l);
+
+            harmonics::cart_pows(l).iter()
+                                   .map(|pows| (order.iter().position(|p| p == pows).unwrap(), 1.0))
+                                   .collect()
+        } else {
+            let order = self.pure_order(l);
+
+            (-l..=l).map(|m| (order.iter().position(|&n| n == m).unwrap(), self.sign(m)))
+                    .collect()
         }
+    }
 
-        let order = get_order(&expns, &coefs)?;
-        let expns = Array2::from_shape_fn((expns.len(), order),
-                                          |(i, j)| expns[i][j]);
-        let coefs = Array2::from_shape_fn((coefs.len(), order),
-                                          |(i, j)| coefs[i][j]);
+    // permutes and flips the mo coefficient columns from this ordering into
+    // mo-draw's own
+    pub fn to_native(self,
+                     atoms:     &Array1<Atom>,
+                     bases:     &Array1<Basis>,
+                     mo_coefs:  &Array2<f64>) -> Result<Array2<f64>, Error> {
+        let mut cols = Vec::with_capacity(mo_coefs.ncols());
 
-        Ok(Self { order, expns, coefs })
+        for atom in atoms {
+            let basis = bases.get(atom.basis_id)
+                             .ok_or("atom refers to a missing basis")?;
+
+            for shell in &basis.shells {
+                let offset = cols.len();
+
+                cols.extend(self.shell_map(shell)
+                                .into_iter()
+                                .map(|(i, sign)| (offset + i, sign)));
+            }
+        }
+
+        if cols.len() != mo_coefs.ncols() {
+            return Err("mo coefficients do not match the basis".into());
+        }
+
+        Ok(Array2::from_shape_fn(mo_coefs.dim(), |(i, j)| {
+            let (col, sign) = cols[j];
+
+            mo_coefs[(i, col)] * sign
+        }))
+    }
+}
+
+// an uncontracted cartesian gaussian, for inputs that expand every mo
+// directly over primitives instead of contracted shells
+#[derive(Debug, Clone)]
+pub struct Primitive {
+    pub atom_idx:   usize,
+    pub pows:       [u32; 3],
+    pub expn:       f64,
+}
+
+impl Primitive {
+    pub fn extent(&self, coef: f64, tolerance: f64) -> f64 {
+        let l = self.pows.iter().sum::<u32>() as i32;
+
+        gaussian_extent(coef, l, self.expn, tolerance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+    use super::*;
+
+    fn shell(l: i32, cart: bool) -> Shell {
+        Shell { l, cart, expns: vec![1.0], coefs: vec![1.0] }
+    }
+
+    #[test]
+    fn p_shells_come_out_y_z_x() {
+        let atoms = array![Atom { basis_id: 0, atomic_num: 1, position: [0.0; 3] }];
+        let bases = array![Basis { shells: vec![shell(1, false)] }];
+
+        for &ordering in AoOrdering::value_variants() {
+            // x, y and z as each program writes them
+            let (x, y, z) = (1.0, 2.0, 3.0);
+            let written = match ordering {
+                AoOrdering::Native => array![[y, z, x]],
+                _ => array![[x, y, z]],
+            };
+            let native = ordering.to_native(&atoms, &bases, &written)
+                                 .unwrap();
+
+            assert_eq!(native, array![[y, z, x]], "{ordering:?}");
+        }
+    }
+
+    #[test]
+    fn shell_maps_are_permutations() {
+        for &ordering in AoOrdering::value_variants() {
+            for l in 0..=harmonics::MAX_L {
+                for cart in [false, true] {
+                    let shell = shell(l, cart);
+                    let mut idxs = ordering.shell_map(&shell)
+                                           .into_iter()
+                                           .map(|(i, _)| i)
+                                           .collect::<Vec<_>>();
+
+                    idxs.sort();
+                    assert_eq!(idxs, (0..shell.num_aos()).collect::<Vec<_>>(),
+                               "{ordering:?} l = {l} cart = {cart}");
+                }
+            }
+        }
     }
 }
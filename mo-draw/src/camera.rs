@@ -0,0 +1,186 @@
+use ndarray::Array1;
+use crate::atom::Atom;
+
+// how far past the outermost nuclei fitting leaves room for, since the
+// orbitals carry on well beyond them
+const FIT_PADDING: f64 = 4.0;
+const MIN_DISTANCE: f64 = 0.5;
+const MAX_DISTANCE: f64 = 1000.0;
+
+// an orbit camera looking at a target from some distance off, which turns
+// around that target rather than around itself
+#[derive(Debug, Clone)]
+pub struct Camera {
+    pub target:     [f64; 3],
+    pub distance:   f64,
+    // columns are the view's right, up and forward directions in world space
+    pub rotation:   [[f64; 3]; 3],
+    // vertical, in radians
+    pub fov:        f64,
+    // how far around the target there's anything to see, so rays can skip
+    // the empty space in front of it
+    pub radius:     f64,
+}
+
+impl Default for Camera {
+    // back down -z looking along +z, and just as far off and as wide as the
+    // fixed camera the shader used to have
+    fn default() -> Self {
+        Self {
+            target:     [0.0; 3],
+            distance:   10.0,
+            rotation:   [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            fov:        2.0 * 0.5f64.atan(),
+            radius:     5.0,
+        }
+    }
+}
+
+impl Camera {
+    // the default orientation, moved so every atom is in view
+    pub fn fitted(atoms: &Array1<Atom>) -> Self {
+        let mut camera = Self::default();
+
+        camera.fit(atoms);
+        camera
+    }
+
+    pub fn right(&self) -> [f64; 3] {
+        self.rotation[0]
+    }
+
+    pub fn up(&self) -> [f64; 3] {
+        self.rotation[1]
+    }
+
+    pub fn forward(&self) -> [f64; 3] {
+        self.rotation[2]
+    }
+
+    pub fn eye(&self) -> [f64; 3] {
+        let forward = self.forward();
+
+        [0, 1, 2].map(|i| self.target[i] - self.distance * forward[i])
+    }
+
+    // where along each ray marching starts
+    pub fn ray_start(&self) -> f64 {
+        (self.distance - self.radius).max(0.0)
+    }
+
+    // camera to world, column by column as gl wants it
+    pub fn view_matrix(&self) -> [[f32; 4]; 4] {
+        let [right, up, forward] = self.rotation;
+        let eye = self.eye();
+        let column = |v: [f64; 3], w: f32| [v[0] as f32, v[1] as f32, v[2] as f32, w];
+
+        [column(right, 0.0), column(up, 0.0), column(forward, 0.0), column(eye, 1.0)]
+    }
+
+    // back to the default orientation, fitted around the atoms
+    pub fn reset(&mut self, atoms: &Array1<Atom>) {
+        *self = Self::fitted(atoms);
+    }
+
+    // keeps the orientation, but centres the atoms and backs off until a
+    // sphere around all of them fits the view
+    pub fn fit(&mut self, atoms: &Array1<Atom>) {
+        if atoms.is_empty() {
+            return;
+        }
+
+        let mut lo = [f64::INFINITY; 3];
+        let mut hi = [f64::NEG_INFINITY; 3];
+
+        for atom in atoms {
+            for i in 0..3 {
+                lo[i] = lo[i].min(atom.position[i]);
+                hi[i] = hi[i].max(atom.position[i]);
+            }
+        }
+
+        let center = [0, 1, 2].map(|i| 0.5 * (lo[i] + hi[i]));
+        let radius = atoms.iter()
+                          .map(|atom| length(sub(atom.position, center)))
+                          .fold(0.0, f64::max)
+                   + FIT_PADDING;
+
+        self.target = center;
+        self.radius = radius;
+        self.distance = (radius / (0.5 * self.fov).sin()).clamp(MIN_DISTANCE, MAX_DISTANCE);
+    }
+
+    // turns around the target, by yaw about the view's up and pitch about
+    // its right, so dragging always moves the picture the same way however
+    // it's been turned
+    pub fn orbit(&mut self, yaw: f64, pitch: f64) {
+        let (up, right) = (self.up(), self.right());
+
+        self.rotate(up, yaw);
+        self.rotate(right, pitch);
+    }
+
+    // moves the target across the view, in fractions of the view's height
+    // so it keeps up with the cursor at any distance
+    pub fn pan(&mut self, dx: f64, dy: f64) {
+        let height = 2.0 * self.distance * (0.5 * self.fov).tan();
+        let (right, up) = (self.right(), self.up());
+
+        for i in 0..3 {
+            self.target[i] += height * (dx * right[i] + dy * up[i]);
+        }
+    }
+
+    // below one moves in, above one backs off
+    pub fn zoom(&mut self, factor: f64) {
+        if factor.is_finite() && factor > 0.0 {
+            self.distance = (self.distance * factor).clamp(MIN_DISTANCE, MAX_DISTANCE);
+        }
+    }
+
+    fn rotate(&mut self, axis: [f64; 3], angle: f64) {
+        self.rotation = self.rotation.map(|v| rotate(v, axis, angle));
+
+        // rounding piles up over many small turns, so square the axes back up
+        let [right, up, _] = self.rotation;
+        let forward = normalize(cross(right, up));
+        let up = normalize(cross(forward, right));
+
+        self.rotation = [normalize(right), up, forward];
+    }
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [0, 1, 2].map(|i| a[i] - b[i])
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn length(v: [f64; 3]) -> f64 {
+    dot(v, v).sqrt()
+}
+
+fn normalize(v: [f64; 3]) -> [f64; 3] {
+    let len = length(v);
+
+    v.map(|x| x / len)
+}
+
+// rodrigues' formula, for a unit axis
+fn rotate(v: [f64; 3], axis: [f64; 3], angle: f64) -> [f64; 3] {
+    let (sin, cos) = angle.sin_cos();
+    let k_cross_v = cross(axis, v);
+    let k_dot_v = dot(axis, v);
+
+    [0, 1, 2].map(|i| v[i] * cos + k_cross_v[i] * sin + axis[i] * k_dot_v * (1.0 - cos))
+}
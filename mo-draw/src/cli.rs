@@ -0,0 +1,272 @@
+use std::{
+    fs,
+    path::PathBuf,
+};
+use clap::{ ArgGroup, Args as ClapArgs, Parser, Subcommand };
+use crate::{
+    basis::AoOrdering,
+    cube::{ self, Grid },
+    drawer::{ Filter, RenderMode },
+    error::Error,
+    info,
+    input::{ Format, Source },
+    mesh::{ Isosurface, MeshFormat },
+    render,
+    validate,
+    Params,
+};
+
+#[derive(Parser, Debug)]
+#[command(name = "CH121 Final MO Drawer")]
+#[command(author = "James Moore <jam0152@uah.edu>")]
+#[command(about = "Visualizes molecular electron orbitals", long_about = None)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+// every subcommand reads its orbitals the same way
+#[derive(ClapArgs, Debug)]
+#[command(group(ArgGroup::new("input")
+                         .required(true)
+                         .multiple(true)
+                         .args(["coefs", "molden", "fchk", "wfn", "scene", "cube", "molfile", "sdf"])))]
+#[command(group(ArgGroup::new("document")
+                         .args(["molden", "fchk", "wfn", "scene", "cube", "molfile", "sdf"])))]
+pub struct Input {
+    #[arg(short = 'B', long = "basis", requires = "coefs")]
+    pub bases: Vec<String>,
+    #[arg(short = 'A', long = "atom", requires = "coefs", conflicts_with_all = ["molfile", "sdf"])]
+    pub atoms: Vec<String>,
+    // the only documents that go with these are molfiles and sdf files,
+    // which stand in for the atoms
+    #[arg(short = 'C', long = "coefs", conflicts_with_all = ["molden", "fchk", "wfn", "scene", "cube"])]
+    pub coefs: Option<String>,
+    // every input below takes a path, '-' for stdin, or unix:<path> to
+    // listen on a socket for the document
+    #[arg(long = "molden")]
+    molden: Option<Source>,
+    #[arg(long = "fchk")]
+    fchk: Option<Source>,
+    // .wfx files are told apart from .wfn by their extension
+    #[arg(long = "wfn")]
+    wfn: Option<Source>,
+    // json, or toml for a .toml extension
+    #[arg(long = "scene")]
+    scene: Option<Source>,
+    // a precomputed volume instead of a basis
+    #[arg(long = "cube")]
+    cube: Option<Source>,
+    // geometry alone for info, or the atoms for --basis and --coefs, one
+    // basis per atom in order; sdf files give their first record
+    #[arg(long = "molfile")]
+    molfile: Option<Source>,
+    #[arg(long = "sdf")]
+    sdf: Option<Source>,
+    // for when the extension can't tell, like on stdin
+    #[arg(long = "format", value_enum, conflicts_with = "coefs")]
+    format: Option<Format>,
+    // for files written by another program than the format implies, like
+    // molden files out of orca
+    #[arg(long = "ordering", value_enum, conflicts_with_all = ["coefs", "scene"])]
+    pub ordering: Option<AoOrdering>,
+}
+
+impl Input {
+    pub fn source(&self) -> Option<(&Source, Format)> {
+        let inputs = [
+            (&self.molden, Format::Molden),
+            (&self.fchk, Format::Fchk),
+            (&self.wfn, Format::Wfn),
+            (&self.scene, Format::Json),
+            (&self.cube, Format::Cube),
+            (&self.molfile, Format::Molfile),
+            (&self.sdf, Format::Sdf),
+        ];
+        let (source, format) = inputs.into_iter()
+                                     .find_map(|(s, f)| Some((s.as_ref()?, f)))?;
+
+        Some((source, self.format.unwrap_or(format.refine(source))))
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    #[command(about = "Opens a window and draws the orbitals")]
+    View {
+        #[command(flatten)]
+        input: Input,
+    },
+    #[command(about = "Prints atoms, shells, AO count and MO energies")]
+    Info {
+        #[command(flatten)]
+        input: Input,
+    },
+    #[command(about = "Checks the input for consistency and normalization")]
+    Validate {
+        #[command(flatten)]
+        input: Input,
+        // fail on warnings too, like mos that aren't normalized
+        #[arg(long = "strict")]
+        strict: bool,
+    },
+    #[command(about = "Writes results to disk without opening a window")]
+    Export {
+        #[command(subcommand)]
+        target: Export,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Export {
+    // draws on a surfaceless egl context where there is one, so it works on
+    // machines with no display server like ci runners on mesa's llvmpipe,
+    // and on the cpu where there isn't
+    #[command(about = "Draws MOs offscreen and writes each to a .png file")]
+    Image {
+        #[command(flatten)]
+        input: Input,
+        // zero-based, every mo if left out
+        #[arg(long = "mo")]
+        mos: Vec<usize>,
+        #[arg(long = "width", default_value_t = 800)]
+        width: u32,
+        #[arg(long = "height", default_value_t = 600)]
+        height: u32,
+        // {mo} is replaced by each mo's index
+        #[arg(short = 'o', long = "output", default_value = "mo-{mo}.png")]
+        output: String,
+        // skip looking for a gl context at all
+        #[arg(long = "cpu")]
+        cpu: bool,
+        // all override the input's own settings
+        #[arg(long = "mode", value_enum)]
+        mode: Option<RenderMode>,
+        #[arg(long = "iso")]
+        iso: Option<f32>,
+        #[arg(long = "density")]
+        density: Option<f32>,
+        // the cpu only ever filters linearly, so cubic needs a gl context
+        #[arg(long = "filter", value_enum)]
+        filter: Option<Filter>,
+        // evaluate each mo onto a grid first, on the gpu only
+        #[arg(long = "precompute")]
+        precompute: bool,
+        // evaluate every ao at every sample, however far away
+        #[arg(long = "no-cull")]
+        no_cull: bool,
+    },
+    #[command(about = "Evaluates one MO on a grid and writes a .cube file")]
+    Cube {
+        #[command(flatten)]
+        input: Input,
+        // zero-based, like the mo_idx the shader sees
+        #[arg(long = "mo")]
+        mo: usize,
+        // both in bohr
+        #[arg(long = "spacing", default_value_t = 0.2)]
+        spacing: f64,
+        #[arg(long = "padding", default_value_t = 4.0)]
+        padding: f64,
+        // stdout if left out
+        #[arg(short = 'o', long = "output")]
+        output: Option<PathBuf>,
+    },
+    #[command(about = "Extracts one MO's +/- isosurface lobes and writes them as a mesh")]
+    Mesh {
+        #[command(flatten)]
+        input: Input,
+        #[arg(long = "mo")]
+        mo: usize,
+        #[arg(long = "iso", default_value_t = 0.05)]
+        iso: f64,
+        #[arg(long = "spacing", default_value_t = 0.15)]
+        spacing: f64,
+        #[arg(long = "padding", default_value_t = 4.0)]
+        padding: f64,
+        // taken from the output's extension if left out, or obj
+        #[arg(long = "mesh-format", value_enum)]
+        mesh_format: Option<MeshFormat>,
+        #[arg(short = 'o', long = "output")]
+        output: Option<PathBuf>,
+    },
+}
+
+fn write_output(output: Option<PathBuf>, text: &str) -> Result<(), Error> {
+    match output {
+        Some(path) => fs::write(path, text)?,
+        None => print!("{text}"),
+    }
+
+    Ok(())
+}
+
+impl Command {
+    pub fn run(self) -> Result<(), Error> {
+        match self {
+            Self::View { input } => crate::view(Params::from_input(input)?)?,
+            Self::Info { input } => {
+                print!("{}", info::format(&Params::geometry_from_input(input)?)?);
+            },
+            Self::Validate { input, strict } => {
+                let report = validate::check(&Params::from_input(input)?);
+
+                print!("{report}");
+
+                if !report.errors.is_empty() || (strict && !report.warnings.is_empty()) {
+                    return Err("input failed validation".into());
+                }
+            },
+            Self::Export { target } => target.run()?,
+        }
+
+        Ok(())
+    }
+}
+
+impl Export {
+    fn run(self) -> Result<(), Error> {
+        match self {
+            Self::Image { input, mos, width, height, output, cpu, mode, iso, density,
+                          filter, precompute, no_cull } => {
+                let mut params = Params::from_input(input)?;
+                let settings = &mut params.settings;
+
+                settings.mode = mode.unwrap_or(settings.mode);
+                settings.iso = iso.unwrap_or(settings.iso);
+                settings.density = density.unwrap_or(settings.density);
+                settings.filter = filter.unwrap_or(settings.filter);
+                settings.precompute.enabled |= precompute;
+                settings.culling.enabled &= !no_cull;
+
+                render::render(&params, &mos, width, height, &output, cpu)
+            },
+            Self::Cube { input, mo, spacing, padding, output } => {
+                let params = Params::from_input(input)?;
+                let grid = Grid::around(&params.atoms, spacing, padding)?;
+                let values = cube::evaluate(&params, mo, &grid)?;
+
+                write_output(output, &cube::write(&params, mo, &grid, &values)?)
+            },
+            Self::Mesh { input, mo, iso, spacing, padding, mesh_format, output } => {
+                let params = Params::from_input(input)?;
+                let grid = Grid::around(&params.atoms, spacing, padding)?;
+                let surface = Isosurface::extract(&params, mo, iso, &grid)?;
+                let format = mesh_format.unwrap_or(MeshFormat::from_path(output.as_deref()));
+
+                write_output(output, &format.write(&surface)?)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+    use super::*;
+
+    #[test]
+    fn args_are_consistent() {
+        Args::command().debug_assert();
+    }
+}
@@ -0,0 +1,648 @@
+use std::{
+    collections::{ HashMap, HashSet },
+    f64::consts::PI,
+    fmt::Write,
+};
+use ndarray::Array1;
+use serde::{ de::value::{ self, StrDeserializer }, Deserialize };
+use winit::event::VirtualKeyCode;
+use crate::{
+    atom::Atom,
+    camera::Camera,
+    drawer::RenderMode,
+    event::{ Button, Frame, HandleEvent, Input, Scroll },
+    mo_coefs::{ MoInfo, Spin },
+    Params,
+};
+
+// held keys turn the camera this many radians a second
+const ORBIT_SPEED: f64 = 0.5 * PI;
+// and zoom by this factor a second
+const ZOOM_SPEED: f64 = 2.0;
+// each wheel notch zooms by this much, and each press of a setting key
+// scales by this, since isovalues and densities span orders of magnitude
+const ZOOM_STEP: f64 = 0.9;
+const SETTING_STEP: f32 = 1.25;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    NextMo,
+    PrevMo,
+    Homo,
+    Lumo,
+    NextMode,
+    ProjectionMode,
+    IsosurfaceMode,
+    VolumeMode,
+    // the isovalue or density, whichever the mode uses
+    Raise,
+    Lower,
+    OrbitLeft,
+    OrbitRight,
+    OrbitUp,
+    OrbitDown,
+    ZoomIn,
+    ZoomOut,
+    ResetCamera,
+    FitCamera,
+    Quit,
+    // for taking a default binding away
+    Unbound,
+}
+
+impl Action {
+    // these keep going for as long as their key is down
+    fn is_held(self) -> bool {
+        matches!(self, Self::OrbitLeft | Self::OrbitRight | Self::OrbitUp
+                     | Self::OrbitDown | Self::ZoomIn | Self::ZoomOut)
+    }
+}
+
+// key names to actions; whatever an input binds goes on top of the defaults
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "HashMap<String, Action>")]
+pub struct Bindings(HashMap<String, Action>);
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let bindings = [
+            ("Right",           Action::NextMo),
+            ("PageDown",        Action::NextMo),
+            ("Left",            Action::PrevMo),
+            ("PageUp",          Action::PrevMo),
+            ("H",               Action::Homo),
+            ("L",               Action::Lumo),
+            ("Tab",             Action::NextMode),
+            ("Key1",            Action::VolumeMode),
+            ("Key2",            Action::IsosurfaceMode),
+            ("Key3",            Action::ProjectionMode),
+            ("Equals",          Action::Raise),
+            ("NumpadAdd",       Action::Raise),
+            ("Minus",           Action::Lower),
+            ("NumpadSubtract",  Action::Lower),
+            ("A",               Action::OrbitLeft),
+            ("D",               Action::OrbitRight),
+            ("W",               Action::OrbitUp),
+            ("S",               Action::OrbitDown),
+            ("Z",               Action::ZoomIn),
+            ("X",               Action::ZoomOut),
+            ("R",               Action::ResetCamera),
+            ("F",               Action::FitCamera),
+            ("Escape",          Action::Quit),
+            ("Q",               Action::Quit),
+        ];
+
+        Self(bindings.into_iter()
+                     .map(|(key, action)| (key.to_string(), action))
+                     .collect())
+    }
+}
+
+impl TryFrom<HashMap<String, Action>> for Bindings {
+    type Error = String;
+
+    // names winit never gives a key would bind nothing, so they're caught
+    // here instead of silently never firing
+    fn try_from(overrides: HashMap<String, Action>) -> Result<Self, String> {
+        for key in overrides.keys() {
+            let name = StrDeserializer::<value::Error>::new(key);
+
+            if VirtualKeyCode::deserialize(name).is_err() {
+                return Err(format!("unknown key {key:?} in bindings, keys go by names like \"Tab\" or \"Key1\""));
+            }
+        }
+
+        let mut bindings = Self::default();
+
+        bindings.0.extend(overrides);
+        Ok(bindings)
+    }
+}
+
+impl Bindings {
+    pub fn action(&self, key: &str) -> Option<Action> {
+        self.0.get(key)
+              .copied()
+              .filter(|&action| action != Action::Unbound)
+    }
+}
+
+// what the drawer should be showing, for the window to pass on each frame
+#[derive(Debug, Clone)]
+pub struct Vars {
+    pub mo_idx:     usize,
+    pub mode:       RenderMode,
+    pub iso:        f32,
+    pub density:    f32,
+    pub camera:     Camera,
+    pub quit:       bool,
+}
+
+pub struct Controller {
+    pub vars:   Vars,
+    bindings:   Bindings,
+    atoms:      Array1<Atom>,
+    mo_info:    Array1<MoInfo>,
+    // physical pixels, which drags and scrolls are measured against
+    size:       (u32, u32),
+    cursor:     Option<(f64, f64)>,
+    dragging:   Option<Button>,
+    shift:      bool,
+    ctrl:       bool,
+    held:       HashSet<Action>,
+    // why the last key did nothing, until the next one
+    note:       Option<&'static str>,
+}
+
+impl Controller {
+    pub fn new(params: &Params) -> Self {
+        let settings = &params.settings;
+
+        Self {
+            vars:       Vars {
+                mo_idx:     0,
+                mode:       settings.mode,
+                iso:        settings.iso,
+                density:    settings.density,
+                camera:     Camera::fitted(&params.atoms),
+                quit:       false,
+            },
+            bindings:   settings.bindings.clone(),
+            atoms:      params.atoms.clone(),
+            mo_info:    params.mo_info.clone(),
+            size:       (0, 0),
+            cursor:     None,
+            dragging:   None,
+            shift:      false,
+            ctrl:       false,
+            held:       HashSet::new(),
+            note:       None,
+        }
+    }
+
+    // what the keys have changed that the picture doesn't show, for the
+    // window title, since stdout may be carrying a document
+    pub fn title(&self) -> String {
+        let vars = &self.vars;
+        let mut title = format!("mo-draw - mo {}", vars.mo_idx);
+
+        if let Some(info) = self.mo_info.get(vars.mo_idx) {
+            if info.spin == Spin::Beta {
+                title.push_str(" beta");
+            }
+
+            if let Some(energy) = info.energy {
+                write!(title, ", energy {energy:.6}").unwrap();
+            }
+        }
+
+        match vars.mode {
+            RenderMode::Projection => title.push_str(", projection"),
+            RenderMode::Isosurface => write!(title, ", isovalue {:.5}", vars.iso).unwrap(),
+            RenderMode::Volume => write!(title, ", density {:.2}", vars.density).unwrap(),
+        }
+
+        if let Some(note) = self.note {
+            write!(title, " - {note}").unwrap();
+        }
+
+        title
+    }
+
+    fn num_mos(&self) -> usize {
+        self.mo_info.len()
+    }
+
+    fn height(&self) -> f64 {
+        self.size.1.max(1) as f64
+    }
+
+    // the highest occupied mo of the same spin as the one shown, going by
+    // index since inputs list mos by energy
+    fn homo(&self) -> Option<usize> {
+        let spin = self.spin();
+
+        self.mo_info.iter()
+                    .enumerate()
+                    .filter(|(_, info)| info.spin == spin && matches!(info.occ, Some(occ) if occ > 0.0))
+                    .map(|(i, _)| i)
+                    .last()
+    }
+
+    fn lumo(&self) -> Option<usize> {
+        let spin = self.spin();
+        let homo = self.homo()?;
+
+        self.mo_info.iter()
+                    .enumerate()
+                    .skip(homo + 1)
+                    .find(|(_, info)| info.spin == spin && matches!(info.occ, Some(occ) if occ <= 0.0))
+                    .map(|(i, _)| i)
+    }
+
+    fn spin(&self) -> Spin {
+        self.mo_info.get(self.vars.mo_idx)
+                    .map_or(Spin::Alpha, |info| info.spin)
+    }
+
+    // the projection has nothing to adjust
+    fn scale_setting(&mut self, steps: i32) {
+        let factor = SETTING_STEP.powi(steps);
+
+        match self.vars.mode {
+            RenderMode::Isosurface => {
+                self.vars.iso = (self.vars.iso * factor).clamp(1e-5, 10.0);
+            },
+            RenderMode::Volume => {
+                self.vars.density = (self.vars.density * factor).clamp(1e-2, 1e5);
+            },
+            RenderMode::Projection => (),
+        }
+    }
+
+    fn press(&mut self, action: Action) {
+        let num_mos = self.num_mos();
+        let vars = &mut self.vars;
+
+        self.note = None;
+
+        match action {
+            Action::NextMo if num_mos > 0 => vars.mo_idx = (vars.mo_idx + 1) % num_mos,
+            Action::PrevMo if num_mos > 0 => vars.mo_idx = (vars.mo_idx + num_mos - 1) % num_mos,
+            Action::Homo => match self.homo() {
+                Some(homo) => self.vars.mo_idx = homo,
+                None => self.note = Some("no occupations to find the homo by"),
+            },
+            Action::Lumo => match self.lumo() {
+                Some(lumo) => self.vars.mo_idx = lumo,
+                None => self.note = Some("no occupations to find the lumo by"),
+            },
+            Action::NextMode => vars.mode = vars.mode.next(),
+            Action::ProjectionMode => vars.mode = RenderMode::Projection,
+            Action::IsosurfaceMode => vars.mode = RenderMode::Isosurface,
+            Action::VolumeMode => vars.mode = RenderMode::Volume,
+            Action::Raise => self.scale_setting(1),
+            Action::Lower => self.scale_setting(-1),
+            Action::ResetCamera => self.vars.camera.reset(&self.atoms),
+            Action::FitCamera => self.vars.camera.fit(&self.atoms),
+            Action::Quit => self.vars.quit = true,
+            _ => (),
+        }
+    }
+
+    // held camera keys, scaled by how long the last frame took
+    fn tick(&mut self, dt: f64) {
+        let camera = &mut self.vars.camera;
+
+        for action in &self.held {
+            match action {
+                Action::OrbitLeft => camera.orbit(-ORBIT_SPEED * dt, 0.0),
+                Action::OrbitRight => camera.orbit(ORBIT_SPEED * dt, 0.0),
+                Action::OrbitUp => camera.orbit(0.0, -ORBIT_SPEED * dt),
+                Action::OrbitDown => camera.orbit(0.0, ORBIT_SPEED * dt),
+                Action::ZoomIn => camera.zoom(ZOOM_SPEED.powf(-dt)),
+                Action::ZoomOut => camera.zoom(ZOOM_SPEED.powf(dt)),
+                _ => (),
+            }
+        }
+    }
+
+    // the left button orbits and the others pan, as does shift with the
+    // left, all in step with the cursor
+    fn drag(&mut self, x: f64, y: f64) {
+        if let (Some(button), Some((last_x, last_y))) = (self.dragging, self.cursor) {
+            let dx = (x - last_x) / self.height();
+            let dy = (y - last_y) / self.height();
+            let camera = &mut self.vars.camera;
+
+            if button == Button::Left && !self.shift {
+                camera.orbit(PI * dx, PI * dy);
+            } else {
+                camera.pan(-dx, dy);
+            }
+        }
+
+        self.cursor = Some((x, y));
+    }
+
+    // wheels zoom, while touchpads only come through as pixel scrolls, which
+    // orbit, pan with shift and zoom with ctrl
+    fn scroll(&mut self, scroll: Scroll) {
+        let height = self.height();
+        let camera = &mut self.vars.camera;
+
+        match scroll {
+            Scroll::Lines(y) => camera.zoom(ZOOM_STEP.powf(y)),
+            Scroll::Pixels(x, y) => {
+                let (dx, dy) = (x / height, y / height);
+
+                if self.ctrl {
+                    camera.zoom(ZOOM_STEP.powf(20.0 * dy));
+                } else if self.shift {
+                    camera.pan(-dx, dy);
+                } else {
+                    camera.orbit(PI * dx, PI * dy);
+                }
+            },
+        }
+    }
+}
+
+impl HandleEvent for Controller {
+    fn handle_input(&mut self, frame: &Frame, input: &Input) {
+        match input {
+            Input::Key { name, pressed } => match self.bindings.action(name) {
+                Some(action) if action.is_held() => {
+                    if *pressed {
+                        self.held.insert(action);
+                    } else {
+                        self.held.remove(&action);
+                    }
+                },
+                Some(action) if *pressed => self.press(action),
+                _ => (),
+            },
+            &Input::Modifiers { shift, ctrl } => {
+                self.shift = shift;
+                self.ctrl = ctrl;
+            },
+            &Input::Button { button, pressed } => {
+                if pressed {
+                    self.dragging = Some(button);
+                } else if self.dragging == Some(button) {
+                    self.dragging = None;
+                }
+            },
+            &Input::CursorMoved { x, y } => self.drag(x, y),
+            Input::CursorLeft => self.cursor = None,
+            &Input::Scroll(scroll) => self.scroll(scroll),
+            &Input::Resized { width, height } => self.size = (width, height),
+            Input::CloseRequested => self.vars.quit = true,
+            Input::Tick => self.tick(frame.dt),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+    use ndarray::{ array, Array1, Array2 };
+    use crate::{ drawer::Settings, Orbitals };
+    use super::*;
+
+    // occupations alternate spins the way unrestricted inputs list them
+    fn params(mo_info: Vec<MoInfo>, settings: Settings) -> Params {
+        let num_mos = mo_info.len();
+
+        Params {
+            atoms:      array![
+                Atom { basis_id: 0, atomic_num: 1, position: [0.0, 0.0, -0.7] },
+                Atom { basis_id: 0, atomic_num: 1, position: [0.0, 0.0, 0.7] },
+            ],
+            orbitals:   Orbitals::Contracted(Array1::from_vec(Vec::new())),
+            mo_coefs:   Array2::zeros((num_mos, 0)),
+            mo_info:    Array1::from_vec(mo_info),
+            settings,
+        }
+    }
+
+    fn mo(occ: f64, spin: Spin) -> MoInfo {
+        MoInfo { energy: None, occ: Some(occ), spin }
+    }
+
+    fn restricted() -> Params {
+        params(vec![mo(2.0, Spin::Alpha), mo(2.0, Spin::Alpha), mo(0.0, Spin::Alpha)],
+               Settings::default())
+    }
+
+    fn frame(dt: f64) -> Frame {
+        Frame { time: Instant::now(), dt }
+    }
+
+    fn key(name: &str, pressed: bool) -> Input {
+        Input::Key { name: name.to_string(), pressed }
+    }
+
+    fn press(controller: &mut Controller, name: &str) {
+        controller.handle_input(&frame(0.0), &key(name, true));
+        controller.handle_input(&frame(0.0), &key(name, false));
+    }
+
+    #[test]
+    fn next_and_prev_mo_wrap_around() {
+        let mut controller = Controller::new(&restricted());
+
+        press(&mut controller, "Left");
+        assert_eq!(controller.vars.mo_idx, 2);
+
+        press(&mut controller, "Right");
+        assert_eq!(controller.vars.mo_idx, 0);
+
+        press(&mut controller, "PageDown");
+        press(&mut controller, "PageDown");
+        press(&mut controller, "PageDown");
+        assert_eq!(controller.vars.mo_idx, 0);
+    }
+
+    #[test]
+    fn homo_and_lumo() {
+        let mut controller = Controller::new(&restricted());
+
+        press(&mut controller, "L");
+        assert_eq!(controller.vars.mo_idx, 2);
+
+        press(&mut controller, "H");
+        assert_eq!(controller.vars.mo_idx, 1);
+    }
+
+    #[test]
+    fn homo_and_lumo_keep_to_the_shown_spin() {
+        let mo_info = vec![
+            mo(1.0, Spin::Alpha),
+            mo(1.0, Spin::Alpha),
+            mo(0.0, Spin::Alpha),
+            mo(1.0, Spin::Beta),
+            mo(0.0, Spin::Beta),
+            mo(0.0, Spin::Beta),
+        ];
+        let mut controller = Controller::new(&params(mo_info, Settings::default()));
+
+        press(&mut controller, "H");
+        assert_eq!(controller.vars.mo_idx, 1);
+
+        press(&mut controller, "L");
+        assert_eq!(controller.vars.mo_idx, 2);
+
+        // over to a beta mo, after which both go by the beta occupations
+        press(&mut controller, "Right");
+        press(&mut controller, "Right");
+        assert_eq!(controller.vars.mo_idx, 4);
+
+        press(&mut controller, "H");
+        assert_eq!(controller.vars.mo_idx, 3);
+
+        press(&mut controller, "L");
+        assert_eq!(controller.vars.mo_idx, 4);
+    }
+
+    #[test]
+    fn homo_needs_occupations() {
+        let mo_info = vec![MoInfo { energy: None, occ: None, spin: Spin::Alpha }; 2];
+        let mut controller = Controller::new(&params(mo_info, Settings::default()));
+
+        press(&mut controller, "Right");
+        press(&mut controller, "H");
+        press(&mut controller, "L");
+        assert_eq!(controller.vars.mo_idx, 1);
+        assert!(controller.title().ends_with("no occupations to find the lumo by"));
+
+        press(&mut controller, "Right");
+        assert!(!controller.title().contains("occupations"));
+    }
+
+    #[test]
+    fn title_follows_the_mo_and_mode() {
+        let mut mo_info = vec![mo(2.0, Spin::Alpha), mo(0.0, Spin::Beta)];
+
+        mo_info[1].energy = Some(0.25);
+
+        let mut controller = Controller::new(&params(mo_info, Settings::default()));
+
+        press(&mut controller, "Right");
+        press(&mut controller, "Key2");
+        let iso = controller.vars.iso;
+
+        assert_eq!(controller.title(),
+                   format!("mo-draw - mo 1 beta, energy 0.250000, isovalue {iso:.5}"));
+
+        press(&mut controller, "Key3");
+        assert_eq!(controller.title(), "mo-draw - mo 1 beta, energy 0.250000, projection");
+    }
+
+    #[test]
+    fn bindings_override_and_unbind_defaults() {
+        let overrides = HashMap::from([
+            ("N".to_string(), Action::NextMo),
+            ("Right".to_string(), Action::Unbound),
+        ]);
+        let settings = Settings {
+            bindings: Bindings::try_from(overrides).unwrap(),
+            ..Settings::default()
+        };
+        let mut controller = Controller::new(&params(restricted().mo_info.to_vec(), settings));
+
+        press(&mut controller, "Right");
+        assert_eq!(controller.vars.mo_idx, 0);
+
+        press(&mut controller, "N");
+        assert_eq!(controller.vars.mo_idx, 1);
+
+        // the defaults that weren't overridden still hold
+        press(&mut controller, "Left");
+        assert_eq!(controller.vars.mo_idx, 0);
+    }
+
+    #[test]
+    fn bindings_reject_unknown_keys() {
+        for name in ["tab", "1", "Ctrl+Q"] {
+            let overrides = HashMap::from([(name.to_string(), Action::Quit)]);
+
+            assert!(Bindings::try_from(overrides).is_err(), "{name}");
+        }
+    }
+
+    #[test]
+    fn modes_and_settings() {
+        let mut controller = Controller::new(&restricted());
+
+        press(&mut controller, "Tab");
+        assert_eq!(controller.vars.mode, RenderMode::Isosurface);
+
+        let iso = controller.vars.iso;
+
+        press(&mut controller, "Equals");
+        assert_eq!(controller.vars.iso, iso * SETTING_STEP);
+
+        press(&mut controller, "Key3");
+        assert_eq!(controller.vars.mode, RenderMode::Projection);
+    }
+
+    #[test]
+    fn held_keys_scale_by_frame_time() {
+        let mut controller = Controller::new(&restricted());
+        let mut camera = controller.vars.camera.clone();
+
+        controller.handle_input(&frame(0.0), &key("A", true));
+        controller.handle_input(&frame(0.25), &Input::Tick);
+        controller.handle_input(&frame(0.5), &Input::Tick);
+        controller.handle_input(&frame(0.0), &key("A", false));
+        controller.handle_input(&frame(1.0), &Input::Tick);
+
+        camera.orbit(-ORBIT_SPEED * 0.25, 0.0);
+        camera.orbit(-ORBIT_SPEED * 0.5, 0.0);
+
+        assert_eq!(controller.vars.camera.rotation, camera.rotation);
+    }
+
+    #[test]
+    fn dragging_orbits_and_pans() {
+        let mut controller = Controller::new(&restricted());
+        let fitted = controller.vars.camera.clone();
+        let mut camera = fitted.clone();
+        let frame = frame(0.0);
+
+        controller.handle_input(&frame, &Input::Resized { width: 200, height: 100 });
+        controller.handle_input(&frame, &Input::CursorMoved { x: 10.0, y: 10.0 });
+        controller.handle_input(&frame, &Input::Button { button: Button::Left, pressed: true });
+        controller.handle_input(&frame, &Input::CursorMoved { x: 60.0, y: 10.0 });
+        controller.handle_input(&frame, &Input::Button { button: Button::Left, pressed: false });
+        // moving without a button held does nothing
+        controller.handle_input(&frame, &Input::CursorMoved { x: 90.0, y: 40.0 });
+
+        camera.orbit(PI * 0.5, 0.0);
+        assert_eq!(controller.vars.camera.rotation, camera.rotation);
+
+        controller.handle_input(&frame, &Input::Button { button: Button::Right, pressed: true });
+        controller.handle_input(&frame, &Input::CursorMoved { x: 90.0, y: 90.0 });
+
+        camera.pan(0.0, 0.5);
+        assert_eq!(controller.vars.camera.target, camera.target);
+
+        press(&mut controller, "R");
+        assert_eq!(controller.vars.camera.rotation, fitted.rotation);
+        assert_eq!(controller.vars.camera.target, fitted.target);
+    }
+
+    #[test]
+    fn scrolling_zooms() {
+        let mut controller = Controller::new(&restricted());
+        let distance = controller.vars.camera.distance;
+        let frame = frame(0.0);
+
+        controller.handle_input(&frame, &Input::Scroll(Scroll::Lines(1.0)));
+        assert_eq!(controller.vars.camera.distance, distance * ZOOM_STEP);
+
+        // touchpads zoom with ctrl, and otherwise leave the distance be
+        controller.handle_input(&frame, &Input::Resized { width: 100, height: 100 });
+        controller.handle_input(&frame, &Input::Scroll(Scroll::Pixels(0.0, 5.0)));
+        assert_eq!(controller.vars.camera.distance, distance * ZOOM_STEP);
+
+        controller.handle_input(&frame, &Input::Modifiers { shift: false, ctrl: true });
+        controller.handle_input(&frame, &Input::Scroll(Scroll::Pixels(0.0, 5.0)));
+        assert_eq!(controller.vars.camera.distance, distance * ZOOM_STEP * ZOOM_STEP);
+    }
+
+    #[test]
+    fn quit() {
+        let mut controller = Controller::new(&restricted());
+
+        press(&mut controller, "Q");
+        assert!(controller.vars.quit);
+
+        let mut controller = Controller::new(&restricted());
+
+        controller.handle_input(&frame(0.0), &Input::CloseRequested);
+        assert!(controller.vars.quit);
+    }
+}
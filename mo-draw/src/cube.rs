@@ -0,0 +1,335 @@
+use std::fmt::Write;
+use ndarray::{ s, Array1, Array2, Array3, Array4, Axis };
+use crate::{
+    atom::{ self, Atom },
+    error::Error,
+    eval::Evaluator,
+    mo_coefs::MoInfo,
+    Orbitals, Params,
+};
+
+// cube files allow any three axes, not just a scaled identity
+#[derive(Debug, Clone)]
+pub struct Grid {
+    pub origin: [f64; 3],
+    pub axes:   [[f64; 3]; 3],
+    pub shape:  [usize; 3],
+}
+
+impl Grid {
+    // an axis-aligned box around every atom, grown by padding on all sides
+    pub fn around(atoms: &Array1<Atom>, spacing: f64, padding: f64) -> Result<Self, Error> {
+        if !spacing.is_finite() || spacing <= 0.0 {
+            return Err("grid spacing must be positive".into());
+        } else if !padding.is_finite() || padding < 0.0 {
+            return Err("grid padding must not be negative".into());
+        } else if atoms.is_empty() {
+            return Err("cannot place a grid without atoms".into());
+        }
+
+        let mut lo = [f64::INFINITY; 3];
+        let mut hi = [f64::NEG_INFINITY; 3];
+
+        for atom in atoms {
+            for i in 0..3 {
+                lo[i] = lo[i].min(atom.position[i] - padding);
+                hi[i] = hi[i].max(atom.position[i] + padding);
+            }
+        }
+
+        let shape = [0, 1, 2].map(|i| ((hi[i] - lo[i]) / spacing).ceil() as usize + 1);
+        let axes = [
+            [spacing, 0.0, 0.0],
+            [0.0, spacing, 0.0],
+            [0.0, 0.0, spacing],
+        ];
+
+        Ok(Self { origin: lo, axes, shape })
+    }
+
+    pub fn point(&self, idx: [usize; 3]) -> [f64; 3] {
+        [0, 1, 2].map(|i| {
+            self.origin[i] + (0..3).map(|j| idx[j] as f64 * self.axes[j][i])
+                                   .sum::<f64>()
+        })
+    }
+
+    // every grid point, one per row, with the last axis running fastest
+    pub fn points(&self) -> Array2<f64> {
+        let [_, ny, nz] = self.shape;
+
+        Array2::from_shape_fn((self.shape.iter().product(), 3), |(n, i)| {
+            self.point([n / (ny * nz), n / nz % ny, n % nz])[i]
+        })
+    }
+
+    // takes a position to fractional grid indices, which the shader needs
+    // to find texels when the axes aren't orthogonal
+    pub fn index_matrix(&self) -> Result<[[f64; 3]; 3], Error> {
+        let [a, b, c] = self.axes;
+        let cross = |u: [f64; 3], v: [f64; 3]| [
+            u[1] * v[2] - u[2] * v[1],
+            u[2] * v[0] - u[0] * v[2],
+            u[0] * v[1] - u[1] * v[0],
+        ];
+        let dot = |u: [f64; 3], v: [f64; 3]| u[0] * v[0] + u[1] * v[1] + u[2] * v[2];
+        let det = dot(a, cross(b, c));
+
+        if det.abs() < 1e-12 {
+            return Err("cube grid axes are degenerate".into());
+        }
+
+        // rows of the inverse are the reciprocal axes
+        Ok([cross(b, c), cross(c, a), cross(a, b)].map(|row| row.map(|x| x / det)))
+    }
+}
+
+// precomputed values, one grid per mo
+#[derive(Debug)]
+pub struct Volume {
+    pub grid:   Grid,
+    // indexed by mo, then the three grid axes
+    pub values: Array4<f64>,
+}
+
+impl Volume {
+    // interpolates between grid points like the shader's linear sampler, so
+    // past the edge values fade out to zero over one more point
+    pub fn sampler(&self, mo_idx: usize) -> Result<impl Fn([f64; 3]) -> f64 + '_, Error> {
+        let recip = self.grid.index_matrix()?;
+        let values = self.values.index_axis(Axis(0), mo_idx);
+
+        Ok(move |pos: [f64; 3]| {
+            let rel = [0, 1, 2].map(|i| pos[i] - self.grid.origin[i]);
+            let idx = recip.map(|r| r[0] * rel[0] + r[1] * rel[1] + r[2] * rel[2]);
+            let base = idx.map(|x| x.floor());
+            let mut value = 0.0;
+
+            for corner in 0..8 {
+                let mut weight = 1.0;
+                let mut texel = [0; 3];
+
+                for i in 0..3 {
+                    let upper = corner >> i & 1 == 1;
+                    let t = idx[i] - base[i];
+                    let n = base[i] as i64 + upper as i64;
+
+                    if n < 0 || n >= self.grid.shape[i] as i64 {
+                        weight = 0.0;
+                    }
+
+                    weight *= if upper { t } else { 1.0 - t };
+                    texel[i] = n.max(0) as usize;
+                }
+
+                if weight != 0.0 {
+                    value += weight * values[texel];
+                }
+            }
+
+            value
+        })
+    }
+}
+
+pub fn evaluate(params: &Params, mo_idx: usize, grid: &Grid) -> Result<Array3<f64>, Error> {
+    let evaluator = Evaluator::new(params)?;
+
+    if mo_idx >= params.mo_coefs.nrows() {
+        return Err(format!("no mo {mo_idx}, there are only {n}",
+                           n = params.mo_coefs.nrows()).into());
+    }
+
+    let mo_coefs = params.mo_coefs.slice(s![mo_idx..=mo_idx, ..]);
+    let values = evaluator.mos(mo_coefs, grid.points().view())?;
+
+    Ok(values.into_shape(grid.shape)?)
+}
+
+// cube values are written like c's %13.5E, which rust's {:E} doesn't give
+fn sci(x: f64) -> String {
+    if x == 0.0 {
+        return format!("{:>13}", "0.00000E+00");
+    }
+
+    let mut exp = x.abs().log10().floor() as i32;
+    let mut mantissa = x / 10f64.powi(exp);
+
+    // rounding can carry the mantissa up to 10
+    if format!("{:.5}", mantissa.abs()).starts_with("10") {
+        exp += 1;
+        mantissa /= 10.0;
+    }
+
+    let sign = if exp < 0 { '-' } else { '+' };
+
+    format!("{:>13}", format!("{mantissa:.5}E{sign}{:02}", exp.abs()))
+}
+
+pub fn write(params: &Params,
+             mo_idx: usize,
+             grid: &Grid,
+             values: &Array3<f64>) -> Result<String, Error> {
+    let mut s = String::new();
+    let info = &params.mo_info[mo_idx];
+
+    writeln!(s, "mo-draw orbital export")?;
+    writeln!(s, "MO {n}, energy {energy}, occupation {occ}",
+             n = mo_idx + 1,
+             energy = info.energy.map_or("unknown".into(), |e| e.to_string()),
+             occ = info.occ.map_or("unknown".into(), |o| o.to_string()))?;
+    writeln!(s, "{:5}{:12.6}{:12.6}{:12.6}",
+             params.atoms.len(), grid.origin[0], grid.origin[1], grid.origin[2])?;
+
+    for (n, axis) in grid.shape.iter().zip(grid.axes) {
+        writeln!(s, "{:5}{:12.6}{:12.6}{:12.6}",
+                 n, axis[0], axis[1], axis[2])?;
+    }
+
+    for atom in &params.atoms {
+        writeln!(s, "{:5}{:12.6}{:12.6}{:12.6}{:12.6}",
+                 atom.atomic_num, atom.atomic_num as f64,
+                 atom.position[0], atom.position[1], atom.position[2])?;
+    }
+
+    // z runs fastest, and every z column starts on a new line
+    for column in values.rows() {
+        for chunk in column.to_vec().chunks(6) {
+            let line = chunk.iter()
+                            .map(|&v| sci(v))
+                            .collect::<String>();
+
+            writeln!(s, "{line}")?;
+        }
+    }
+
+    Ok(s)
+}
+
+fn parse_line<T: std::str::FromStr>(line: Option<&str>, len: usize) -> Result<Vec<T>, Error>
+where
+    T::Err: std::error::Error + 'static,
+{
+    let fields = line.ok_or("truncated cube header")?
+                     .split_whitespace()
+                     .take(len)
+                     .collect::<Vec<_>>();
+
+    if fields.len() < len {
+        return Err("invalid cube header line".into());
+    }
+
+    let mut v = Vec::with_capacity(len);
+
+    for field in fields {
+        v.push(field.parse()?);
+    }
+
+    Ok(v)
+}
+
+pub fn parse(text: &str) -> Result<Params, Error> {
+    // the first two lines are free-form comments
+    let mut lines = text.lines()
+                        .skip(2);
+
+    let line = lines.next()
+                    .ok_or("truncated cube header")?;
+    let fields = line.split_whitespace()
+                     .collect::<Vec<_>>();
+
+    if fields.len() < 4 {
+        return Err("invalid cube header line".into());
+    }
+
+    // a negative atom count means an mo list follows the atoms
+    let num_atoms: i64 = fields[0].parse()?;
+    let mut origin = [fields[1].parse()?, fields[2].parse()?, fields[3].parse()?];
+    let mut num_vals: usize = fields.get(4)
+                                    .map(|s| s.parse())
+                                    .transpose()?
+                                    .unwrap_or(1);
+
+    let mut shape = [0; 3];
+    let mut axes = [[0.0; 3]; 3];
+    let mut scale = 1.0;
+
+    for i in 0..3 {
+        let fields = parse_line::<f64>(lines.next(), 4)?;
+
+        // and a negative point count means angstrom
+        if fields[0] < 0.0 {
+            scale = atom::ANGSTROM;
+        }
+
+        shape[i] = fields[0].abs() as usize;
+        axes[i] = [fields[1], fields[2], fields[3]];
+    }
+
+    if shape.contains(&0) {
+        return Err("cube grid is empty".into());
+    }
+
+    let mut atoms = Vec::with_capacity(num_atoms.unsigned_abs() as usize);
+
+    for _ in 0..num_atoms.unsigned_abs() {
+        let fields = parse_line::<f64>(lines.next(), 5)?;
+
+        atoms.push(Atom {
+            basis_id:   0,
+            atomic_num: fields[0] as u32,
+            position:   [fields[2], fields[3], fields[4]].map(|x| x * scale),
+        });
+    }
+
+    let mut tokens = lines.flat_map(|line| line.split_whitespace());
+
+    if num_atoms < 0 {
+        let num_mos: usize = tokens.next()
+                                   .ok_or("cube file has no mo list")?
+                                   .parse()?;
+
+        // the mo numbers themselves only label the values
+        for _ in 0..num_mos {
+            tokens.next()
+                  .ok_or("truncated cube mo list")?;
+        }
+
+        num_vals = num_mos;
+    }
+
+    if num_vals == 0 {
+        return Err("cube file has no values per point".into());
+    }
+
+    let len = shape.iter().product::<usize>() * num_vals;
+    let mut values = Vec::with_capacity(len);
+
+    for token in tokens {
+        values.push(token.parse::<f64>()?);
+    }
+
+    if values.len() != len {
+        return Err(format!("cube file has {} values, expected {len}", values.len()).into());
+    }
+
+    // the value index runs fastest in the file, but slowest in a volume
+    let values = Array4::from_shape_vec((shape[0], shape[1], shape[2], num_vals), values)?
+                        .permuted_axes([3, 0, 1, 2])
+                        .as_standard_layout()
+                        .into_owned();
+
+    origin = origin.map(|x| x * scale);
+    axes = axes.map(|axis| axis.map(|x| x * scale));
+
+    let volume = Volume { grid: Grid { origin, axes, shape }, values };
+
+    // a volume has no aos, so every mo row is empty
+    Ok(Params {
+        atoms:      atoms.into(),
+        orbitals:   Orbitals::Volume(volume),
+        mo_coefs:   Array2::zeros((num_vals, 0)),
+        mo_info:    vec![MoInfo::default(); num_vals].into(),
+        settings:   Default::default(),
+    })
+}
@@ -0,0 +1,207 @@
+// everything it takes to evaluate an mo from its aos, shared by the
+// fragment and compute shaders, which declare mo_idx ahead of this
+#if defined(PRIMITIVE_AOS)
+struct Primitive {
+    vec3            org;
+    ivec3           pows;
+    float           expn;
+};
+
+// drawer/buffers.rs lays these out and picks the bindings
+layout(std430, binding = 0) readonly buffer Primitives {
+    Primitive       primitives[];
+};
+
+#define num_ao primitives.length()
+#else
+struct Orbital {
+    vec3            org;
+    int             ang_idx;
+    int             prim_idx;
+    int             num_prims;
+};
+
+layout(std430, binding = 0) readonly buffer Orbitals {
+    Orbital         orbitals[];
+};
+
+// every shell's primitives back to back, orbitals index into these
+layout(std430, binding = 1) readonly buffer BasExpns {
+    float           bas_expns[];
+};
+
+layout(std430, binding = 2) readonly buffer BasCoefs {
+    float           bas_coefs[];
+};
+
+#define num_ao orbitals.length()
+#endif
+
+// one row of num_ao after another, and inputs are free to carry more or
+// fewer mos than aos
+layout(std430, binding = 3) readonly buffer MoCoefs {
+    float           mo_coefs[];
+};
+
+#if defined(CULL_AOS)
+// cells of a uniform grid over every ao's reach, last axis fastest
+layout(std430, binding = 4) readonly buffer CellGrid {
+    vec3            cell_org;
+    float           cell_len;
+    ivec3           cell_dims;
+};
+
+// each cell's start and count in cell_aos
+layout(std430, binding = 5) readonly buffer CellRanges {
+    ivec2           cell_ranges[];
+};
+
+layout(std430, binding = 6) readonly buffer CellAos {
+    int             cell_aos[];
+};
+
+// the aos that reach pos, as a range of cell_aos, where outside the grid
+// nothing does
+ivec2 ao_range(vec3 pos) {
+    ivec3 cell = ivec3(floor((pos - cell_org) / cell_len));
+
+    if (any(lessThan(cell, ivec3(0))) || any(greaterThanEqual(cell, cell_dims))) {
+        return ivec2(0);
+    }
+
+    return cell_ranges[(cell.x * cell_dims.y + cell.y) * cell_dims.z + cell.z];
+}
+
+int ao_at(int i) {
+    return cell_aos[i];
+}
+#else
+ivec2 ao_range(vec3 pos) {
+    return ivec2(0, num_ao);
+}
+
+int ao_at(int i) {
+    return i;
+}
+#endif
+
+#if defined(PRIMITIVE_AOS)
+// pow() is undefined for negative bases
+float ipow(float x, int n) {
+    float y = 1.0;
+
+    for (int i = 0; i < n; i++) {
+        y *= x;
+    }
+
+    return y;
+}
+
+// primitive coefficients already include normalization
+float wave_fn(vec3 pos, int ao_idx) {
+    pos -= primitives[ao_idx].org;
+    ivec3 pows = primitives[ao_idx].pows;
+    float expn = primitives[ao_idx].expn;
+
+    return ipow(pos.x, pows.x) * ipow(pos.y, pows.y) * ipow(pos.z, pows.z)
+         * exp(-expn * dot(pos, pos));
+}
+
+vec3 wave_fn_gradient(vec3 pos, int ao_idx) {
+    pos -= primitives[ao_idx].org;
+    ivec3 pows = primitives[ao_idx].pows;
+    float expn = primitives[ao_idx].expn;
+    vec3 powers = vec3(ipow(pos.x, pows.x), ipow(pos.y, pows.y), ipow(pos.z, pows.z));
+    // each power's own derivative, which is zero for a zero power
+    vec3 d_powers = vec3(pows.x > 0 ? pows.x * ipow(pos.x, pows.x - 1) : 0.0,
+                         pows.y > 0 ? pows.y * ipow(pos.y, pows.y - 1) : 0.0,
+                         pows.z > 0 ? pows.z * ipow(pos.z, pows.z - 1) : 0.0);
+    float radial = exp(-expn * dot(pos, pos));
+    float ang = powers.x * powers.y * powers.z;
+
+    return radial * (vec3(d_powers.x * powers.y * powers.z,
+                          powers.x * d_powers.y * powers.z,
+                          powers.x * powers.y * d_powers.z)
+                     - 2.0 * expn * pos * ang);
+}
+#else
+// cases are generated from harmonics, so l goes as high as MAX_L for both
+// solid harmonics and cartesian components
+// also, ang_idx must be in lock-step or this will be really slow
+float angular(vec3 pos, int ang_idx) {
+    switch (ang_idx) {
+@ANGULAR
+    }
+
+    return 0.0;
+}
+
+float wave_fn(vec3 pos, int ao_idx) {
+    pos -= orbitals[ao_idx].org;
+    float r2 = dot(pos, pos);
+    int prim_idx = orbitals[ao_idx].prim_idx;
+    int num_prims = orbitals[ao_idx].num_prims;
+    float radial = 0.0;
+
+    // primitive norms are folded into the coefficients ahead of time
+    for (int i = prim_idx; i < prim_idx + num_prims; i++) {
+        radial += bas_coefs[i] * exp(-bas_expns[i] * r2);
+    }
+
+    return radial * angular(pos, orbitals[ao_idx].ang_idx);
+}
+
+vec3 angular_gradient(vec3 pos, int ang_idx) {
+    switch (ang_idx) {
+@ANGULAR_GRADIENT
+    }
+
+    return vec3(0.0);
+}
+
+vec3 wave_fn_gradient(vec3 pos, int ao_idx) {
+    pos -= orbitals[ao_idx].org;
+    float r2 = dot(pos, pos);
+    int prim_idx = orbitals[ao_idx].prim_idx;
+    int num_prims = orbitals[ao_idx].num_prims;
+    int ang_idx = orbitals[ao_idx].ang_idx;
+    float radial = 0.0;
+    // the radial part's gradient is this times pos
+    float d_radial = 0.0;
+
+    for (int i = prim_idx; i < prim_idx + num_prims; i++) {
+        float g = bas_coefs[i] * exp(-bas_expns[i] * r2);
+
+        radial += g;
+        d_radial -= 2.0 * bas_expns[i] * g;
+    }
+
+    return d_radial * pos * angular(pos, ang_idx) + radial * angular_gradient(pos, ang_idx);
+}
+#endif
+
+float lcao_wave_fn(vec3 pos) {
+    float w = 0.0;
+    ivec2 range = ao_range(pos);
+
+    for (int i = range.x; i < range.x + range.y; i++) {
+        int ao_idx = ao_at(i);
+
+        w += wave_fn(pos, ao_idx) * mo_coefs[mo_idx * num_ao + ao_idx];
+    }
+
+    return w;
+}
+
+vec3 lcao_gradient(vec3 pos) {
+    vec3 g = vec3(0.0);
+    ivec2 range = ao_range(pos);
+
+    for (int i = range.x; i < range.x + range.y; i++) {
+        int ao_idx = ao_at(i);
+
+        g += wave_fn_gradient(pos, ao_idx) * mo_coefs[mo_idx * num_ao + ao_idx];
+    }
+
+    return g;
+}
@@ -0,0 +1,328 @@
+use ndarray::{ Array1, Array2, Axis };
+use crate::{
+    atom::Atom,
+    basis::{ Basis, Primitive },
+    harmonics,
+};
+
+// binding points of the shader's storage buffers, which have to stay in
+// step with aos.glsl
+pub const AOS_BINDING: u32 = 0;
+pub const BAS_EXPNS_BINDING: u32 = 1;
+pub const BAS_COEFS_BINDING: u32 = 2;
+pub const MO_COEFS_BINDING: u32 = 3;
+pub const CELL_GRID_BINDING: u32 = 4;
+pub const CELL_RANGES_BINDING: u32 = 5;
+pub const CELL_AOS_BINDING: u32 = 6;
+
+// past this the cells grow instead, since most would be empty anyway
+const MAX_CELLS: usize = 1 << 18;
+
+// laid out like the shader's std430 Orbital, where the vec3 takes the
+// alignment of a vec4 and the whole struct pads out to a multiple of it
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct GpuOrbital {
+    org:        [f32; 3],
+    ang_idx:    i32,
+    prim_idx:   i32,
+    num_prims:  i32,
+    _pad:       [i32; 2],
+}
+
+// and its CellGrid, which is just the one struct
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct GpuCellGrid {
+    org:        [f32; 3],
+    cell_len:   f32,
+    dims:       [i32; 3],
+    _pad:       i32,
+}
+
+// and its Primitive, where the ivec3 starts on the next vec4
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct GpuPrimitive {
+    org:        [f32; 3],
+    _pad:       f32,
+    pows:       [i32; 3],
+    expn:       f32,
+}
+
+// aos point into flat primitive arrays, so shells can differ in length and
+// every m and every atom sharing a shell share its primitives too
+pub fn orbitals(atoms: &Array1<Atom>,
+                bases: &Array1<Basis>) -> (Vec<GpuOrbital>, Vec<f32>, Vec<f32>) {
+    let mut expns = Vec::new();
+    let mut coefs = Vec::new();
+    let mut prim_idxs = Vec::with_capacity(bases.len());
+
+    for basis in bases {
+        let mut idxs = Vec::with_capacity(basis.shells.len());
+
+        for shell in &basis.shells {
+            idxs.push(expns.len());
+            expns.extend(shell.expns.iter().map(|&x| x as f32));
+            coefs.extend(shell.norm_coefs().into_iter().map(|x| x as f32));
+        }
+
+        prim_idxs.push(idxs);
+    }
+
+    let mut orbitals = Vec::new();
+
+    for atom in atoms {
+        let basis = &bases[atom.basis_id];
+        let org = atom.position.map(|x| x as f32);
+
+        for (shell, &prim_idx) in basis.shells.iter().zip(&prim_idxs[atom.basis_id]) {
+            let l = shell.l;
+            let num_prims = shell.expns.len();
+            // cartesian components come after every solid harmonic
+            let ang_idxs = if shell.cart {
+                (0..shell.num_aos()).map(|k| harmonics::NUM_LM + harmonics::cart_index(l, k))
+                                    .collect::<Vec<_>>()
+            } else {
+                (-l..=l).map(|m| harmonics::lm_index(l, m))
+                        .collect()
+            };
+
+            for ang_idx in ang_idxs {
+                orbitals.push(GpuOrbital {
+                    org,
+                    ang_idx:    ang_idx as i32,
+                    prim_idx:   prim_idx as i32,
+                    num_prims:  num_prims as i32,
+                    _pad:       [0; 2],
+                });
+            }
+        }
+    }
+
+    (orbitals, expns, coefs)
+}
+
+pub fn primitives(atoms: &Array1<Atom>,
+                  prims: &Array1<Primitive>) -> Vec<GpuPrimitive> {
+    prims.iter()
+         .map(|prim| GpuPrimitive {
+             org:    atoms[prim.atom_idx].position.map(|x| x as f32),
+             _pad:   0.0,
+             pows:   prim.pows.map(|n| n as i32),
+             expn:   prim.expn as f32,
+         })
+         .collect()
+}
+
+// one mo after another, each as long as there are aos
+pub fn mo_coefs(mo_coefs: &Array2<f64>) -> Vec<f32> {
+    mo_coefs.iter()
+            .map(|&x| x as f32)
+            .collect()
+}
+
+// the largest coefficient each ao has in any mo, which is as much as it can
+// add to a sample
+fn max_coefs(mo_coefs: &Array2<f64>) -> Vec<f64> {
+    mo_coefs.axis_iter(Axis(1))
+            .map(|col| col.iter().fold(0.0, |max: f64, c| max.max(c.abs())))
+            .collect()
+}
+
+// each ao's center and how far out it still matters, in the same order as
+// orbitals() lays them out
+pub fn orbital_extents(atoms:       &Array1<Atom>,
+                       bases:       &Array1<Basis>,
+                       mo_coefs:    &Array2<f64>,
+                       tolerance:   f64) -> Vec<([f64; 3], f64)> {
+    let mut coefs = max_coefs(mo_coefs).into_iter();
+    let mut extents = Vec::new();
+
+    for atom in atoms {
+        for shell in &bases[atom.basis_id].shells {
+            for coef in coefs.by_ref().take(shell.num_aos()) {
+                extents.push((atom.position, shell.extent(coef, tolerance)));
+            }
+        }
+    }
+
+    extents
+}
+
+pub fn primitive_extents(atoms:     &Array1<Atom>,
+                         prims:     &Array1<Primitive>,
+                         mo_coefs:  &Array2<f64>,
+                         tolerance: f64) -> Vec<([f64; 3], f64)> {
+    prims.iter()
+         .zip(max_coefs(mo_coefs))
+         .map(|(prim, coef)| (atoms[prim.atom_idx].position, prim.extent(coef, tolerance)))
+         .collect()
+}
+
+// a uniform grid of cells over every ao's reach, each listing the aos whose
+// sphere touches it as a start and count into one flat array of indices
+pub fn cells(extents:   &[([f64; 3], f64)],
+             cell_len:  f64) -> (GpuCellGrid, Vec<[i32; 2]>, Vec<i32>) {
+    let mut lo = [f64::INFINITY; 3];
+    let mut hi = [f64::NEG_INFINITY; 3];
+
+    for &(org, r) in extents {
+        for i in 0..3 {
+            lo[i] = lo[i].min(org[i] - r);
+            hi[i] = hi[i].max(org[i] + r);
+        }
+    }
+
+    // no aos at all still needs a cell to say so
+    if extents.is_empty() {
+        lo = [0.0; 3];
+        hi = [0.0; 3];
+    }
+
+    let count = |len: f64| [0, 1, 2].map(|i| ((hi[i] - lo[i]) / len).ceil().max(1.0) as usize);
+    let mut cell_len = cell_len;
+    let mut dims = count(cell_len);
+    let mut num_cells = dims.iter().product::<usize>();
+
+    while num_cells > MAX_CELLS {
+        cell_len *= (num_cells as f64 / MAX_CELLS as f64).cbrt().max(1.01);
+        dims = count(cell_len);
+        num_cells = dims.iter().product();
+    }
+
+    let [_, ny, nz] = dims;
+    let mut lists = vec![Vec::new(); num_cells];
+
+    for (ao_idx, &(org, r)) in extents.iter().enumerate() {
+        // only the cells inside the sphere's bounding box can touch it
+        let first = [0, 1, 2].map(|i| ((org[i] - r - lo[i]) / cell_len).floor().max(0.0) as usize);
+        let last = [0, 1, 2].map(|i| {
+            (((org[i] + r - lo[i]) / cell_len).floor().max(0.0) as usize).min(dims[i] - 1)
+        });
+
+        for x in first[0]..=last[0] {
+            for y in first[1]..=last[1] {
+                for z in first[2]..=last[2] {
+                    let idx = [x, y, z];
+                    // from the sphere's center to the nearest point of the cell
+                    let dist2 = (0..3).map(|i| {
+                                          let min = lo[i] + idx[i] as f64 * cell_len;
+                                          let d = (min - org[i]).max(org[i] - min - cell_len)
+                                                                .max(0.0);
+
+                                          d * d
+                                      })
+                                      .sum::<f64>();
+
+                    if dist2 <= r * r {
+                        lists[(x * ny + y) * nz + z].push(ao_idx as i32);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut ranges = Vec::with_capacity(num_cells);
+    let mut aos = Vec::new();
+
+    for list in lists {
+        ranges.push([aos.len() as i32, list.len() as i32]);
+        aos.extend(list);
+    }
+
+    let grid = GpuCellGrid {
+        org:        lo.map(|x| x as f32),
+        cell_len:   cell_len as f32,
+        dims:       dims.map(|n| n as i32),
+        _pad:       0,
+    };
+
+    (grid, ranges, aos)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+    use crate::{
+        basis::Shell,
+        drawer::Settings,
+        eval::Evaluator,
+        Orbitals, Params,
+    };
+    use super::*;
+
+    // the aos a point's cell lists, looked up the way aos.glsl does
+    fn aos_at(cells: &(GpuCellGrid, Vec<[i32; 2]>, Vec<i32>), pos: [f64; 3]) -> Vec<i32> {
+        let (grid, ranges, aos) = cells;
+        let cell = [0, 1, 2].map(|i| {
+            ((pos[i] as f32 - grid.org[i]) / grid.cell_len).floor() as i32
+        });
+
+        if (0..3).any(|i| cell[i] < 0 || cell[i] >= grid.dims[i]) {
+            return Vec::new();
+        }
+
+        let [_, ny, nz] = grid.dims;
+        let [start, len] = ranges[((cell[0] * ny + cell[1]) * nz + cell[2]) as usize];
+
+        aos[start as usize..(start + len) as usize].to_vec()
+    }
+
+    #[test]
+    fn cells_only_list_aos_that_reach_them() {
+        let extents = [([0.0; 3], 1.5), ([4.0, 0.0, 0.0], 1.0)];
+        let cells = cells(&extents, 0.5);
+
+        assert_eq!(cells.0.dims, [13, 6, 6]);
+        assert_eq!(aos_at(&cells, [0.0; 3]), [0]);
+        assert_eq!(aos_at(&cells, [1.25, 0.1, 0.1]), [0]);
+        assert_eq!(aos_at(&cells, [4.0, 0.0, 0.0]), [1]);
+        // inside the first ao's bounding box, but the whole cell is past its
+        // reach, as is the gap between the two
+        assert!(aos_at(&cells, [1.25, 1.25, 1.25]).is_empty());
+        assert!(aos_at(&cells, [2.25, 0.1, 0.1]).is_empty());
+        assert!(aos_at(&cells, [9.0, 0.0, 0.0]).is_empty());
+    }
+
+    #[test]
+    fn cells_grow_past_max_cells() {
+        let extents = [([0.0; 3], 1.0), ([200.0, 200.0, 200.0], 1.0)];
+        let (grid, ranges, _) = cells(&extents, 0.5);
+
+        assert!(ranges.len() <= MAX_CELLS);
+        assert_eq!(ranges.len(), grid.dims.iter().product::<i32>() as usize);
+        assert!(grid.cell_len > 0.5);
+    }
+
+    #[test]
+    fn aos_stay_under_tolerance_past_their_extents() {
+        let tolerance = 1e-6;
+        let shells = vec![
+            Shell { l: 0, cart: false, expns: vec![3.4, 0.6, 0.17], coefs: vec![0.2, 0.5, 0.4] },
+            Shell { l: 2, cart: false, expns: vec![0.8], coefs: vec![1.0] },
+            Shell { l: 3, cart: true, expns: vec![0.5], coefs: vec![1.0] },
+        ];
+        let num_aos = 1 + 5 + 10;
+        let params = Params {
+            atoms:      array![Atom { basis_id: 0, atomic_num: 6, position: [0.5, -0.5, 0.0] }],
+            orbitals:   Orbitals::Contracted(array![Basis { shells }]),
+            mo_coefs:   Array2::from_elem((2, num_aos), 0.4),
+            mo_info:    Array1::from_vec(Vec::new()),
+            settings:   Settings::default(),
+        };
+        let Orbitals::Contracted(bases) = &params.orbitals else { unreachable!() };
+        let extents = orbital_extents(&params.atoms, bases, &params.mo_coefs, tolerance);
+        let aos = Evaluator::new(&params).unwrap().aos;
+
+        assert_eq!(extents.len(), num_aos);
+
+        for (ao, &(org, r)) in aos.iter().zip(&extents) {
+            for dir in [[1.0, 0.0, 0.0], [0.0, 0.6, 0.8], [0.577, 0.577, 0.577]] {
+                let pos = [0, 1, 2].map(|i| org[i] + 1.01 * r * dir[i]);
+
+                assert!(0.4 * ao.value(pos).abs() < tolerance, "{:e} at {r}", ao.value(pos));
+            }
+        }
+    }
+}
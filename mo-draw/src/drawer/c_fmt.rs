@@ -1,65 +1,61 @@
-use ndarray::{ Array1, ArrayBase, Ix1, Ix2, RawData, Data };
-use crate::{
-    atom::Atom,
-    basis::{ self, Basis },
-};
+use crate::harmonics::{ self, Term };
 
-pub fn array1<S, T>(array: &ArrayBase<S, Ix1>) -> String
-where
-    S: RawData<Elem = T> + Data,
-    T: ToString,
-{
-    let s = array.iter()
-                 .map(|x| x.to_string())
-                 .collect::<Vec<String>>()
-                 .join(", ");
+fn polynomial(terms: &[Term]) -> String {
+    if terms.is_empty() {
+        return String::from("0.0");
+    }
 
-    format!("{{ {s} }}")
-}
+    terms.iter()
+         .map(|term| {
+             let mut factors = vec![format!("{:?}", term.coef)];
 
-pub fn array2<S, T>(array: &ArrayBase<S, Ix2>) -> String
-where
-    S: RawData<Elem = T> + Data,
-    T: ToString,
-{
-    let s = array.rows()
-                 .into_iter()
-                 .map(|r| array1(&r))
-                 .collect::<Vec<String>>()
-                 .join(", ");
+             for (c, &n) in ["pos.x", "pos.y", "pos.z"].iter().zip(&term.pows) {
+                 factors.extend((0..n).map(|_| c.to_string()));
+             }
 
-    format!("{{ {s} }}")
+             format!("({})", factors.join(" * "))
+         })
+         .collect::<Vec<String>>()
+         .join(" + ")
 }
 
-pub fn orbitals(atoms: &Array1<Atom>,
-                bases: &Array1<Basis>) -> String {
-    let mut v = Vec::new();
-
-    for atom in atoms {
-        let basis = &bases[atom.basis_id];
-        let num_expns = basis.expns.shape()[0];
-        let pos = format!("{{ {x}, {y}, {z} }}",
-                          x = atom.position[0],
-                          y = atom.position[1],
-                          z = atom.position[2]);
-
-        for n in 1..=(num_expns as i32) {
-            let bas_expns = array1(&basis.expns.row(n as usize - 1));
-
-            for l in 0..n {
-                let nl_idx = basis::nl_index(n, l);
-
-                for m in -l..=l {
-                    let bas_coefs = array1(&basis.coefs.row(nl_idx));
-                    let lm_idx = basis::lm_index(l, m);
-                    let entry = format!("{{ {pos}, {lm_idx}, {bas_expns}, {bas_coefs} }}");
+// one switch case per ang_idx: solid harmonics, then cartesian components
+pub fn angular_cases() -> String {
+    let sph = harmonics::table()
+                        .into_iter()
+                        .map(|terms| polynomial(&terms));
+    let cart = harmonics::cart_table()
+                         .into_iter()
+                         .map(|term| polynomial(&[term]));
+
+    sph.chain(cart)
+       .enumerate()
+       .map(|(ang_idx, poly)| format!("    case {ang_idx}:\n        return {poly};"))
+       .collect::<Vec<String>>()
+       .join("\n")
+}
 
-                    v.push(entry);
-                }
-            }
-        }
-    }
+// the same cases again, but for the gradient of each angular part
+pub fn angular_gradient_cases() -> String {
+    let sph = harmonics::table();
+    let cart = harmonics::cart_table()
+                         .into_iter()
+                         .map(|term| vec![term]);
+
+    sph.into_iter()
+       .chain(cart)
+       .enumerate()
+       .map(|(ang_idx, terms)| {
+           let [dx, dy, dz] = [0, 1, 2].map(|axis| polynomial(&harmonics::derivative(&terms, axis)));
+
+           format!("    case {ang_idx}:\n        return vec3({dx}, {dy}, {dz});")
+       })
+       .collect::<Vec<String>>()
+       .join("\n")
+}
 
-    format!("{{ {s} }}", s = v.join(", "))
+// debug formatting keeps the decimal point on whole floats
+pub fn vec3(v: [f64; 3]) -> String {
+    format!("vec3({:?}, {:?}, {:?})", v[0], v[1], v[2])
 }
 
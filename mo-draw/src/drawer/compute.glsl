@@ -0,0 +1,35 @@
+#version 450
+@DEFINES
+
+layout(local_size_x = 4, local_size_y = 4, local_size_z = 4) in;
+
+// laid out like a volume's texture, with the grid's last axis first
+#ifdef VOLUME_GRADIENTS
+layout(rgba32f, binding = 0) uniform writeonly image3D values;
+#else
+layout(r32f, binding = 0) uniform writeonly image3D values;
+#endif
+
+uniform int mo_idx;
+// the first grid point, and the steps along each grid axis as columns
+uniform vec3 vol_org;
+uniform mat3 vol_axes;
+
+@AOS
+
+void main() {
+    ivec3 texel = ivec3(gl_GlobalInvocationID);
+
+    // the last work groups hang over the edges
+    if (any(greaterThanEqual(texel, imageSize(values)))) {
+        return;
+    }
+
+    vec3 pos = vol_org + vol_axes * vec3(texel.zyx);
+
+#ifdef VOLUME_GRADIENTS
+    imageStore(values, texel, vec4(lcao_wave_fn(pos), lcao_gradient(pos)));
+#else
+    imageStore(values, texel, vec4(lcao_wave_fn(pos)));
+#endif
+}
@@ -0,0 +1,328 @@
+use ndarray::{ arr1, s, Array1, Array2, ArrayView2, Axis };
+use crate::{
+    camera::Camera,
+    error::Error,
+    eval::Evaluator,
+    mesh,
+    Orbitals, Params,
+};
+use super::{ Renderer, RenderMode };
+
+// these have to stay in step with frag_template.glsl
+const NUM_BISECTIONS: usize = 8;
+const GRAD_STEP: f64 = 0.01;
+const LIGHT: [f64; 3] = [-0.4, 0.6, -1.0];
+const MIN_TRANSMIT: f64 = 0.01;
+
+// draws the same images as the fragment shader, only with the evaluator, for
+// when there's no gl context to be had; it evaluates every ao at every sample,
+// so it ignores precomputing and culling, and samples volumes linearly
+// whatever the filter
+pub struct CpuDrawer<'a> {
+    params:     &'a Params,
+    // volume inputs sample their grid instead
+    evaluator:  Option<Evaluator>,
+    camera:     Camera,
+    size:       (u32, u32),
+    pixels:     Vec<u8>,
+    mode:       RenderMode,
+    iso:        f64,
+    density:    f64,
+}
+
+impl<'a> CpuDrawer<'a> {
+    pub fn new(params: &'a Params) -> Result<Self, Error> {
+        let evaluator = match &params.orbitals {
+            Orbitals::Volume(volume) => {
+                // degenerate axes are the only way sampling can fail
+                volume.grid.index_matrix()?;
+
+                None
+            },
+            _ => {
+                let evaluator = Evaluator::new(params)?;
+
+                if evaluator.aos.len() != params.mo_coefs.ncols() {
+                    return Err("mo coefficients do not match the basis".into());
+                }
+
+                Some(evaluator)
+            },
+        };
+
+        Ok(Self {
+            params,
+            evaluator,
+            camera:     Camera::fitted(&params.atoms),
+            size:       (0, 0),
+            pixels:     Vec::new(),
+            mode:       params.settings.mode,
+            iso:        params.settings.iso as f64,
+            density:    params.settings.density as f64,
+        })
+    }
+
+    // the direction of every ray through one image row
+    fn row_rays(&self, row: u32) -> Vec<[f64; 3]> {
+        let (width, height) = self.size;
+        // gl_FragCoord runs from the bottom up, through pixel centers
+        let frag_y = (height - 1 - row) as f64 + 0.5;
+        let scale = 2.0 * (0.5 * self.camera.fov).tan();
+
+        (0..width).map(|col| {
+                      let frag_x = col as f64 + 0.5;
+                      let uv = [
+                          (frag_x - 0.5 * width as f64) / height as f64,
+                          (frag_y - 0.5 * height as f64) / height as f64,
+                      ];
+
+                      self.view_dir(normalize([scale * uv[0], scale * uv[1], 1.0]))
+                  })
+                  .collect()
+    }
+
+    // from the camera's frame to the world's
+    fn view_dir(&self, v: [f64; 3]) -> [f64; 3] {
+        let [right, up, forward] = self.camera.rotation;
+
+        [0, 1, 2].map(|i| v[0] * right[i] + v[1] * up[i] + v[2] * forward[i])
+    }
+
+    fn ray_point(&self, rd: [f64; 3], rm: f64) -> [f64; 3] {
+        let eye = self.camera.eye();
+
+        [0, 1, 2].map(|i| eye[i] + rd[i] * rm)
+    }
+
+    // every point along one image row's rays, ray after ray, each starting
+    // with the camera itself like the shader's first trapezoid does
+    fn row_points(&self, rays: &[[f64; 3]]) -> Array2<f64> {
+        let settings = &self.params.settings;
+        let num_points = settings.num_steps as usize + 1;
+        let step_len = settings.step_len as f64;
+        let ray_start = self.camera.ray_start();
+
+        let mut points = Array2::zeros((rays.len() * num_points, 3));
+
+        for (n, rd) in rays.iter().enumerate() {
+            for i in 0..num_points {
+                let rm = if i == 0 { 0.0 } else { ray_start + (i - 1) as f64 * step_len };
+
+                points.row_mut(n * num_points + i)
+                      .assign(&arr1(&self.ray_point(*rd, rm)));
+            }
+        }
+
+        points
+    }
+
+    fn lcao_wave_fn(&self, mo_idx: usize, points: ArrayView2<f64>) -> Array1<f64> {
+        match (&self.evaluator, &self.params.orbitals) {
+            (Some(evaluator), _) => {
+                let mo_coefs = self.params.mo_coefs.slice(s![mo_idx..=mo_idx, ..]);
+
+                evaluator.mos(mo_coefs, points)
+                         .expect("coefficients were checked against the basis")
+                         .column(0)
+                         .to_owned()
+            },
+            (None, Orbitals::Volume(volume)) => {
+                let sample = volume.sampler(mo_idx)
+                                   .expect("grid axes were checked for degeneracy");
+
+                points.rows()
+                      .into_iter()
+                      .map(|p| sample([p[0], p[1], p[2]]))
+                      .collect()
+            },
+            (None, _) => unreachable!(),
+        }
+    }
+
+    fn lcao_gradient(&self, mo_idx: usize, points: ArrayView2<f64>) -> Array2<f64> {
+        if let Some(evaluator) = &self.evaluator {
+            let mo_coefs = self.params.mo_coefs.slice(s![mo_idx..=mo_idx, ..]);
+
+            return evaluator.mo_gradients(mo_coefs, points)
+                            .expect("coefficients were checked against the basis")
+                            .index_axis_move(Axis(1), 0);
+        }
+
+        // volumes only have differences to go on, like in the shader
+        let mut grads = Array2::zeros((points.nrows(), 3));
+
+        for axis in 0..3 {
+            let mut ahead = points.to_owned();
+            let mut behind = points.to_owned();
+
+            ahead.column_mut(axis)
+                 .mapv_inplace(|x| x + GRAD_STEP);
+            behind.column_mut(axis)
+                  .mapv_inplace(|x| x - GRAD_STEP);
+
+            let diff = self.lcao_wave_fn(mo_idx, ahead.view()) - self.lcao_wave_fn(mo_idx, behind.view());
+
+            grads.column_mut(axis)
+                 .assign(&(diff / (2.0 * GRAD_STEP)));
+        }
+
+        grads
+    }
+
+    fn draw_projection(&self, mo_idx: usize, row: u32, pixels: &mut Vec<u8>) {
+        let num_points = self.params.settings.num_steps as usize + 1;
+        let step_len = self.params.settings.step_len as f64;
+        let values = self.lcao_wave_fn(mo_idx, self.row_points(&self.row_rays(row)).view());
+
+        for ray in values.as_slice().unwrap().chunks_exact(num_points) {
+            let q = ray.windows(2)
+                       .map(|w| 0.5 * step_len * (w[0] + w[1]))
+                       .sum::<f64>();
+
+            pixels.extend([to_channel(q * q * 10.0); 3]);
+            pixels.push(255);
+        }
+    }
+
+    // the shader's ray_volume, which stops compositing at the same point
+    fn draw_volume(&self, mo_idx: usize, row: u32, pixels: &mut Vec<u8>) {
+        let num_points = self.params.settings.num_steps as usize + 1;
+        let step_len = self.params.settings.step_len as f64;
+        let values = self.lcao_wave_fn(mo_idx, self.row_points(&self.row_rays(row)).view());
+
+        for ray in values.as_slice().unwrap().chunks_exact(num_points) {
+            let mut transmit = 1.0;
+            let mut color = [0.0; 3];
+
+            // the first point is the camera's, which only the projection uses
+            for &w in &ray[1..] {
+                if transmit <= MIN_TRANSMIT {
+                    break;
+                }
+
+                let alpha = 1.0 - (-self.density * w * w * step_len).exp();
+                let emit = if w > 0.0 { mesh::POSITIVE_COLOR } else { mesh::NEGATIVE_COLOR };
+
+                for (c, e) in color.iter_mut().zip(emit) {
+                    *c += transmit * alpha * e;
+                }
+
+                transmit *= 1.0 - alpha;
+            }
+
+            pixels.extend(color.map(to_channel));
+            pixels.push(255);
+        }
+    }
+
+    // the shader's ray_isosurface, with every ray in the row bisecting at once
+    fn draw_isosurface(&self, mo_idx: usize, row: u32, pixels: &mut Vec<u8>) {
+        let num_points = self.params.settings.num_steps as usize + 1;
+        let step_len = self.params.settings.step_len as f64;
+        let ray_start = self.camera.ray_start();
+        let rays = self.row_rays(row);
+        let values = self.lcao_wave_fn(mo_idx, self.row_points(&rays).view());
+
+        // which rays hit, and between which distances along them
+        let mut hits = Vec::new();
+
+        for (n, ray) in values.as_slice().unwrap().chunks_exact(num_points).enumerate() {
+            if let Some(i) = (1..num_points).find(|&i| ray[i].abs() >= self.iso) {
+                let hi = ray_start + (i - 1) as f64 * step_len;
+                let lo = if i == 1 { hi } else { hi - step_len };
+
+                hits.push((n, ray[i].signum(), lo, hi));
+            }
+        }
+
+        for _ in 0..NUM_BISECTIONS {
+            let mids = Array2::from_shape_fn((hits.len(), 3), |(h, i)| {
+                let (n, _, lo, hi) = hits[h];
+
+                self.ray_point(rays[n], 0.5 * (lo + hi))[i]
+            });
+            let values = self.lcao_wave_fn(mo_idx, mids.view());
+
+            for ((_, phase, lo, hi), w) in hits.iter_mut().zip(values) {
+                let mid = 0.5 * (*lo + *hi);
+
+                if *phase * w >= self.iso {
+                    *hi = mid;
+                } else {
+                    *lo = mid;
+                }
+            }
+        }
+
+        let points = Array2::from_shape_fn((hits.len(), 3), |(h, i)| {
+            let (n, _, _, hi) = hits[h];
+
+            self.ray_point(rays[n], hi)[i]
+        });
+        let grads = self.lcao_gradient(mo_idx, points.view());
+        // the light moves with the camera, so the side in view is always lit
+        let light = self.view_dir(normalize(LIGHT));
+        let mut colors = vec![[0.0; 3]; rays.len()];
+
+        for (&(n, phase, _, _), grad) in hits.iter().zip(grads.rows()) {
+            let normal = normalize([0, 1, 2].map(|i| -phase * grad[i]));
+            let diffuse = dot(normal, light).max(0.0);
+            // light reflected about the normal, against the ray
+            let reflected = [0, 1, 2].map(|i| 2.0 * dot(normal, light) * normal[i] - light[i]);
+            let specular = dot(reflected, rays[n].map(|x| -x)).max(0.0)
+                                                               .powi(32);
+            let base = if phase > 0.0 { mesh::POSITIVE_COLOR } else { mesh::NEGATIVE_COLOR };
+
+            colors[n] = base.map(|c| c * (0.25 + 0.75 * diffuse) + 0.3 * specular);
+        }
+
+        for color in colors {
+            pixels.extend(color.map(to_channel));
+            pixels.push(255);
+        }
+    }
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalize(v: [f64; 3]) -> [f64; 3] {
+    let len = dot(v, v).sqrt();
+
+    v.map(|x| x / len)
+}
+
+// what gl does writing a float color to an 8 bit channel
+fn to_channel(x: f64) -> u8 {
+    (x.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+impl Renderer for CpuDrawer<'_> {
+    fn draw_mo(&mut self, mo_idx: usize) {
+        let (width, height) = self.size;
+        let mut pixels = Vec::with_capacity(4 * (width * height) as usize);
+
+        for row in 0..height {
+            match self.mode {
+                RenderMode::Projection => self.draw_projection(mo_idx, row, &mut pixels),
+                RenderMode::Isosurface => self.draw_isosurface(mo_idx, row, &mut pixels),
+                RenderMode::Volume => self.draw_volume(mo_idx, row, &mut pixels),
+            }
+        }
+
+        self.pixels = pixels;
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.size = (width, height);
+    }
+
+    fn set_camera(&mut self, camera: &Camera) {
+        self.camera = camera.clone();
+    }
+
+    fn read_pixels(&self) -> Vec<u8> {
+        self.pixels.clone()
+    }
+}
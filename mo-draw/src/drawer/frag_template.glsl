@@ -1,81 +1,112 @@
-#version 460
+#version 450
+@DEFINES
 
 const float pi = 3.1415927;
-const float step_len = @STEP_LEN;
-const int num_steps = @NUM_STEPS;
-const int num_cc = @NUM_CC;
-
-struct Orbital {
-    vec3            org;
-    int             lm_idx;
-    float[num_cc]   bas_expns;
-    float[num_cc]   bas_coefs;
-};
-
-const Orbital[] orbitals = @ORBITALS;
-const int num_ao = orbitals.length();
-const int num_mo = num_ao; // feels more expressive
-const float[num_mo][num_ao] mo_coefs = @MO_COEFS;
 
-uniform vec2 resolution;
-uniform int mo_idx;
-
-out vec4 f_color;
+uniform float step_len;
+uniform int num_steps;
 
-// works for l on [0, 2]
-// also, lm_idx must be in lock-step or this will be really slow
-float re_sph(vec3 pos, int lm_idx) {
-    float r = pos.length();
-
-    switch (lm_idx) {
-    case 0:
-        return sqrt(0.25    / pi);
-    case 1:
-        return sqrt(0.75    / pi) * (pos.y / r);
-    case 2:
-        return sqrt(0.75    / pi) * (pos.z / r);
-    case 3:
-        return sqrt(0.75    / pi) * (pos.x / r);
-    case 4:
-        return sqrt(0.375   / pi) * (pos.x * pos.y / (r * r));
-    case 5:
-        return sqrt(0.375   / pi) * (pos.y * pos.z / (r * r));
-    case 6:
-        return sqrt(0.3125  / pi) * (3.0 * pos.z * pos.z / (r * r) - 1.0);
-    case 7:
-        return sqrt(0.375   / pi) * (pos.x * pos.z / (r * r));
-    case 8:
-        return sqrt(0.9375  / pi) * ((pos.x * pos.x) - (pos.y * pos.y)) / (r * r);
-    }
-}
+// which lobe is which in isosurface and volume mode, the same as exported
+// meshes
+const vec3 positive_color = @POSITIVE_COLOR;
+const vec3 negative_color = @NEGATIVE_COLOR;
+// how finely hits on the isosurface get pinned down between two steps
+const int num_bisections = 8;
+// volumes without gradients fall back on differences for their normals
+const float grad_step = 0.01;
+// rays stop once this little of the background would show through
+const float min_transmit = 0.01;
 
-float wave_fn(vec3 pos, int ao_idx) {
-    pos -= orbitals[ao_idx].org;
-    float r2 = dot(pos, pos);
-    float radial = 0.0;
+const int MODE_PROJECTION = 0;
+const int MODE_ISOSURFACE = 1;
+const int MODE_VOLUME = 2;
 
-    for (int i = 0; i < num_cc; i++) {
-        float coef = orbitals[ao_idx].bas_coefs[i];
-        float expn = orbitals[ao_idx].bas_expns[i];
-        radial += coef * pow(2.0 * expn / pi, 0.75) * exp(-expn * r2);
-    }
+uniform vec2 resolution;
+uniform int mo_idx;
+uniform int mode;
+uniform float iso;
+uniform float density;
+// camera to world, and the vertical field of view in radians
+uniform mat4 view;
+uniform float fov;
+// how far out from the camera there's anything to see
+uniform float ray_start;
 
+out vec4 f_color;
 
-    return radial * re_sph(pos, orbitals[ao_idx].lm_idx);
+#if defined(VOLUME)
+uniform vec3 vol_org;
+// columns are the reciprocal axes, so a vector times this is in grid indices
+uniform mat3 vol_idx;
+
+// one texture per mo, bound ahead of each draw, unless it's an mo filled in
+// by the compute shader
+uniform sampler3D volume;
+
+// texels are laid out z fastest like the cube file, so the grid's first
+// axis is the texture's last, and border texels read as zero
+vec4 sample_volume(vec3 pos) {
+    vec3 idx = ((pos - vol_org) * vol_idx).zyx;
+    vec3 size = vec3(textureSize(volume, 0));
+
+#ifdef CUBIC_FILTER
+    // a cubic b-spline out of eight linear fetches, after sigg and hadwiger,
+    // which smooths a little as well as filtering
+    vec3 index = floor(idx);
+    vec3 t = idx - index;
+    vec3 s = 1.0 - t;
+    vec3 w0 = s * s * s / 6.0;
+    vec3 w1 = 2.0 / 3.0 - 0.5 * t * t * (2.0 - t);
+    vec3 w2 = 2.0 / 3.0 - 0.5 * s * s * (2.0 - s);
+    vec3 w3 = t * t * t / 6.0;
+    vec3 g0 = w0 + w1;
+    vec3 g1 = w2 + w3;
+    // where to fetch so linear filtering does the weighting within each pair
+    vec3 h0 = (index + w1 / g0 - 0.5) / size;
+    vec3 h1 = (index + w3 / g1 + 1.5) / size;
+
+    vec4 v0 = mix(mix(texture(volume, vec3(h1.x, h1.y, h1.z)),
+                      texture(volume, vec3(h0.x, h1.y, h1.z)), g0.x),
+                  mix(texture(volume, vec3(h1.x, h0.y, h1.z)),
+                      texture(volume, vec3(h0.x, h0.y, h1.z)), g0.x), g0.y);
+    vec4 v1 = mix(mix(texture(volume, vec3(h1.x, h1.y, h0.z)),
+                      texture(volume, vec3(h0.x, h1.y, h0.z)), g0.x),
+                  mix(texture(volume, vec3(h1.x, h0.y, h0.z)),
+                      texture(volume, vec3(h0.x, h0.y, h0.z)), g0.x), g0.y);
+
+    return mix(v0, v1, g0.z);
+#else
+    return texture(volume, (idx + 0.5) / size);
+#endif
 }
 
 float lcao_wave_fn(vec3 pos) {
-    float w = 0.0;
-
-    for (int i = 0; i < num_ao; i++) {
-        w += wave_fn(pos, i) * mo_coefs[mo_idx][i];
-    }
+    return sample_volume(pos).r;
+}
 
-    return w;
+#ifdef VOLUME_GRADIENTS
+// the compute shader can store the analytic gradient alongside each value
+vec3 lcao_gradient(vec3 pos) {
+    return sample_volume(pos).gba;
 }
+#else
+vec3 lcao_gradient(vec3 pos) {
+    vec2 h = vec2(grad_step, 0.0);
 
-float ray_integral(vec3 ro, vec3 rd) {
-    float rm = 5.0;
+    return vec3(lcao_wave_fn(pos + h.xyy) - lcao_wave_fn(pos - h.xyy),
+                lcao_wave_fn(pos + h.yxy) - lcao_wave_fn(pos - h.yxy),
+                lcao_wave_fn(pos + h.yyx) - lcao_wave_fn(pos - h.yyx)) / (2.0 * grad_step);
+}
+#endif
+#else
+@AOS
+#endif
+
+// drawer/cpu.rs marches these same rays, so keep the two in step
+// this squares the integral of psi rather than integrating psi squared, so
+// lobes of opposite phase cancel each other out along the ray
+float ray_projection(vec3 ro, vec3 rd) {
+    float rm = ray_start;
     float q = 0.0;
     float wa = lcao_wave_fn(ro);
     float wb = 0.0;
@@ -93,11 +124,81 @@ float ray_integral(vec3 ro, vec3 rd) {
     return q * q;
 }
 
+// marches out to the first place the wave function reaches +iso or -iso,
+// then bisects the last step down to the surface and shades it by which
+// lobe it belongs to; drawer/cpu.rs does the same
+vec3 ray_isosurface(vec3 ro, vec3 rd) {
+    float rm = ray_start;
+
+    for (int i = 0; i < num_steps; i++) {
+        float w = lcao_wave_fn(ro + rd * rm);
+
+        if (abs(w) >= iso) {
+            float phase = sign(w);
+            float lo = i == 0 ? rm : rm - step_len;
+            float hi = rm;
+
+            for (int j = 0; j < num_bisections; j++) {
+                float mid = 0.5 * (lo + hi);
+
+                if (phase * lcao_wave_fn(ro + rd * mid) >= iso) {
+                    hi = mid;
+                } else {
+                    lo = mid;
+                }
+            }
+
+            vec3 pos = ro + rd * hi;
+            // out of the lobe, which is down the gradient of phase * psi
+            vec3 n = -phase * normalize(lcao_gradient(pos));
+            // the light moves with the camera, so the side in view is lit
+            vec3 light = mat3(view) * normalize(vec3(-0.4, 0.6, -1.0));
+            float diffuse = max(dot(n, light), 0.0);
+            float specular = pow(max(dot(reflect(-light, n), -rd), 0.0), 32.0);
+            vec3 color = phase > 0.0 ? positive_color : negative_color;
+
+            return color * (0.25 + 0.75 * diffuse) + vec3(0.3 * specular);
+        }
+
+        rm += step_len;
+    }
+
+    return vec3(0.0);
+}
+
+// emission and absorption, composited front to back: psi squared sets how
+// opaque each step is and the phase which colour it glows
+vec3 ray_volume(vec3 ro, vec3 rd) {
+    float rm = ray_start;
+    float transmit = 1.0;
+    vec3 color = vec3(0.0);
+
+    for (int i = 0; i < num_steps && transmit > min_transmit; i++) {
+        float w = lcao_wave_fn(ro + rd * rm);
+        float alpha = 1.0 - exp(-density * w * w * step_len);
+
+        color += transmit * alpha * (w > 0.0 ? positive_color : negative_color);
+        transmit *= 1.0 - alpha;
+
+        rm += step_len;
+    }
+
+    return color;
+}
+
 void main() {
+    // uv spans one unit top to bottom, which the field of view stretches
     vec2 uv = (gl_FragCoord.xy - 0.5 * resolution) / resolution.y;
-    vec3 ro = vec3(0.0, 0.0, -10.0);
-    vec3 rd = normalize(vec3(uv, 1.0));
-    float q = ray_integral(ro, rd);
+    vec3 ro = view[3].xyz;
+    vec3 rd = mat3(view) * normalize(vec3(uv * 2.0 * tan(0.5 * fov), 1.0));
 
-    f_color = vec4(vec3(q) * 10.0, 1.0);
+    if (mode == MODE_VOLUME) {
+        f_color = vec4(ray_volume(ro, rd), 1.0);
+    } else if (mode == MODE_ISOSURFACE) {
+        f_color = vec4(ray_isosurface(ro, rd), 1.0);
+    } else {
+        float q = ray_projection(ro, rd);
+
+        f_color = vec4(vec3(q) * 10.0, 1.0);
+    }
 }
@@ -2,14 +2,19 @@ use std::{
     ffi::{ CStr, CString },
     marker::PhantomData, mem, ptr,
 };
+use ndarray::ArrayView3;
 use crate::{
+    camera::Camera,
+    controller::Bindings,
+    cube::Grid,
     error::Error,
     gl::{ self, types::* },
-    Params
+    mesh,
+    Orbitals, Params,
 };
 use glutin::display::{ Display, GlDisplay };
-
-mod c_fmt;
+use clap::ValueEnum;
+use serde::Deserialize;
 
 macro_rules! c_str {
     ($s:literal) => {
@@ -17,6 +22,14 @@ macro_rules! c_str {
     }
 }
 
+mod buffers;
+mod c_fmt;
+mod cpu;
+mod precompute;
+
+pub use cpu::CpuDrawer;
+use precompute::Precomputer;
+
 struct Shader {
     gl_handle:  GLuint,
 }
@@ -66,28 +79,88 @@ impl Drop for Shader {
     }
 }
 
+// fills in a shader template; the angular functions are the same for every
+// basis, so they can be written out whether or not the preprocessor keeps them
+fn expand(template: &str, defines: &str) -> String {
+    const AOS_SRC: &str = include_str!("aos.glsl");
+
+    String::from(template)
+           .replace("@AOS", AOS_SRC)
+           .replace("@DEFINES", defines)
+           .replace("@ANGULAR_GRADIENT", &c_fmt::angular_gradient_cases())
+           .replace("@ANGULAR", &c_fmt::angular_cases())
+           .replace("@POSITIVE_COLOR", &c_fmt::vec3(mesh::POSITIVE_COLOR))
+           .replace("@NEGATIVE_COLOR", &c_fmt::vec3(mesh::NEGATIVE_COLOR))
+}
+
+// how aos.glsl finds the aos, for whichever shader evaluates them
+fn aos_defines(params: &Params) -> Vec<&'static str> {
+    let mut defines = Vec::new();
+
+    if let Orbitals::Primitive(_) = params.orbitals {
+        defines.push("#define PRIMITIVE_AOS");
+    }
+
+    if params.settings.culling.enabled {
+        defines.push("#define CULL_AOS");
+    }
+
+    defines
+}
+
 struct Program {
     gl_handle:  GLuint,
+    // which kind of input it was built for, and how it samples
+    defines:    String,
+    step_len:   GLint,
+    num_steps:  GLint,
+    vol_org:    GLint,
+    vol_idx:    GLint,
     resolution: GLint,
     mo_idx:     GLint,
+    mode:       GLint,
+    iso:        GLint,
+    density:    GLint,
+    view:       GLint,
+    fov:        GLint,
+    ray_start:  GLint,
 }
 
 impl Program {
-    fn new(params: &Params) -> Result<Self, Error> {
+    // the shader only depends on what kind of orbitals there are and how
+    // they're sampled; precomputed mos read like volumes
+    fn defines(params: &Params) -> String {
+        let settings = &params.settings;
+        let precompute = &settings.precompute;
+        let mut defines = Vec::new();
+
+        match &params.orbitals {
+            Orbitals::Volume(_) => defines.push("#define VOLUME"),
+            _ if precompute.enabled => {
+                defines.push("#define VOLUME");
+
+                if precompute.gradients {
+                    defines.push("#define VOLUME_GRADIENTS");
+                }
+            },
+            _ => defines.extend(aos_defines(params)),
+        }
+
+        if settings.filter == Filter::Cubic {
+            defines.push("#define CUBIC_FILTER");
+        }
+
+        defines.join("\n")
+    }
+
+    fn new(defines: String) -> Result<Self, Error> {
         unsafe {
             const VERT_SRC: &'static str = include_str!("vert.glsl");
             let vert = Shader::new(gl::VERTEX_SHADER, VERT_SRC)?;
 
             const FRAG_TEMPLATE: &'static str = include_str!("frag_template.glsl");
-            let num_cc = params.bases[0].order.to_string();
-            let orbitals = c_fmt::orbitals(&params.atoms, &params.bases);
-            let mo_coefs = c_fmt::array2(&params.mo_coefs);
-            let frag_src = String::from(FRAG_TEMPLATE)
-                                  .replace("@STEP_LEN", "0.1")
-                                  .replace("@NUM_STEPS", "200")
-                                  .replace("@NUM_CC", &num_cc)
-                                  .replace("@ORBITALS", &orbitals)
-                                  .replace("@MO_COEFS", &mo_coefs);
+            let frag_src = expand(FRAG_TEMPLATE, &defines);
+
             let frag = Shader::new(gl::FRAGMENT_SHADER, frag_src.as_str())?;
 
             let gl_handle = gl::CreateProgram();
@@ -95,10 +168,35 @@ impl Program {
             gl::AttachShader(gl_handle, frag.gl_handle);
             gl::LinkProgram(gl_handle);
 
+            let step_len = gl::GetUniformLocation(gl_handle, c_str!("step_len"));
+            let num_steps = gl::GetUniformLocation(gl_handle, c_str!("num_steps"));
+            let vol_org = gl::GetUniformLocation(gl_handle, c_str!("vol_org"));
+            let vol_idx = gl::GetUniformLocation(gl_handle, c_str!("vol_idx"));
             let resolution = gl::GetUniformLocation(gl_handle, c_str!("resolution"));
             let mo_idx = gl::GetUniformLocation(gl_handle, c_str!("mo_idx"));
-
-            Ok(Self { gl_handle, resolution, mo_idx })
+            let mode = gl::GetUniformLocation(gl_handle, c_str!("mode"));
+            let iso = gl::GetUniformLocation(gl_handle, c_str!("iso"));
+            let density = gl::GetUniformLocation(gl_handle, c_str!("density"));
+            let view = gl::GetUniformLocation(gl_handle, c_str!("view"));
+            let fov = gl::GetUniformLocation(gl_handle, c_str!("fov"));
+            let ray_start = gl::GetUniformLocation(gl_handle, c_str!("ray_start"));
+
+            Ok(Self {
+                gl_handle,
+                defines,
+                step_len,
+                num_steps,
+                vol_org,
+                vol_idx,
+                resolution,
+                mo_idx,
+                mode,
+                iso,
+                density,
+                view,
+                fov,
+                ray_start,
+            })
         }
     }
 
@@ -106,6 +204,26 @@ impl Program {
         unsafe { gl::UseProgram(self.gl_handle); }
     }
 
+    fn set_steps(&self, step_len: f32, num_steps: u32) {
+        unsafe {
+            gl::Uniform1f(self.step_len, step_len);
+            gl::Uniform1i(self.num_steps, num_steps as i32);
+        }
+    }
+
+    fn set_volume(&self, grid: &Grid) -> Result<(), Error> {
+        let org = grid.origin.map(|x| x as f32);
+        let idx = grid.index_matrix()?
+                      .map(|column| column.map(|x| x as f32));
+
+        unsafe {
+            gl::Uniform3fv(self.vol_org, 1, org.as_ptr());
+            gl::UniformMatrix3fv(self.vol_idx, 1, gl::FALSE, idx.as_ptr() as *const _);
+        }
+
+        Ok(())
+    }
+
     fn set_resolution(&self, width: f32, height: f32) {
         unsafe { gl::Uniform2f(self.resolution, width, height); }
     }
@@ -113,6 +231,28 @@ impl Program {
     fn set_mo_idx(&self, mo_idx: i32) {
         unsafe { gl::Uniform1i(self.mo_idx, mo_idx); }
     }
+
+    fn set_mode(&self, mode: RenderMode) {
+        unsafe { gl::Uniform1i(self.mode, mode as i32); }
+    }
+
+    fn set_iso(&self, iso: f32) {
+        unsafe { gl::Uniform1f(self.iso, iso); }
+    }
+
+    fn set_density(&self, density: f32) {
+        unsafe { gl::Uniform1f(self.density, density); }
+    }
+
+    fn set_camera(&self, camera: &Camera) {
+        let view = camera.view_matrix();
+
+        unsafe {
+            gl::UniformMatrix4fv(self.view, 1, gl::FALSE, view.as_ptr() as *const _);
+            gl::Uniform1f(self.fov, camera.fov as f32);
+            gl::Uniform1f(self.ray_start, camera.ray_start() as f32);
+        }
+    }
 }
 
 impl Drop for Program {
@@ -238,6 +378,177 @@ impl<T> Drop for VertexBuffer<T> {
     }
 }
 
+// read-only data for the shader, at a fixed binding point
+struct StorageBuffer {
+    gl_handle:  GLuint,
+    binding:    GLuint,
+}
+
+impl StorageBuffer {
+    fn from_slice<T>(binding: u32, slice: &[T]) -> Self {
+        let size = mem::size_of_val(slice);
+
+        unsafe {
+            let mut gl_handle = mem::zeroed();
+
+            gl::GenBuffers(1, &mut gl_handle);
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, gl_handle);
+            gl::BufferData(gl::SHADER_STORAGE_BUFFER,
+                           size as GLsizeiptr,
+                           slice.as_ptr() as *const _,
+                           gl::STATIC_DRAW);
+
+            Self { gl_handle, binding }
+        }
+    }
+
+    fn bind(&self) {
+        unsafe { gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, self.binding, self.gl_handle); }
+    }
+}
+
+impl Drop for StorageBuffer {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteBuffers(1, &self.gl_handle); }
+    }
+}
+
+// what each texel of a volume holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TexelFormat {
+    // just the value
+    R32f,
+    // the value, then its gradient
+    Rgba32f,
+}
+
+impl TexelFormat {
+    // how gl stores it, which is also what image units need
+    fn internal(self) -> GLenum {
+        match self {
+            Self::R32f => gl::R32F,
+            Self::Rgba32f => gl::RGBA32F,
+        }
+    }
+
+    // and how data uploaded to it is laid out
+    fn pixel(self) -> GLenum {
+        match self {
+            Self::R32f => gl::RED,
+            Self::Rgba32f => gl::RGBA,
+        }
+    }
+}
+
+// a float volume, sampled linearly and zero outside
+struct Texture3d {
+    gl_handle:  GLuint,
+}
+
+impl Texture3d {
+    fn from_array(array: &ArrayView3<f64>) -> Self {
+        let data = array.iter()
+                        .map(|&x| x as f32)
+                        .collect::<Vec<_>>();
+
+        Self::new(array.dim(), TexelFormat::R32f, data.as_ptr())
+    }
+
+    // left for a compute shader to fill in
+    fn empty(dim: (usize, usize, usize), format: TexelFormat) -> Self {
+        Self::new(dim, format, ptr::null())
+    }
+
+    fn new(dim: (usize, usize, usize), format: TexelFormat, data: *const f32) -> Self {
+        let (depth, height, width) = dim;
+
+        unsafe {
+            let mut gl_handle = mem::zeroed();
+
+            gl::GenTextures(1, &mut gl_handle);
+            gl::BindTexture(gl::TEXTURE_3D, gl_handle);
+            gl::TexImage3D(gl::TEXTURE_3D, 0,
+                           format.internal() as GLint,
+                           width as GLsizei,
+                           height as GLsizei,
+                           depth as GLsizei,
+                           0, format.pixel(), gl::FLOAT,
+                           data as *const _);
+
+            for wrap in [gl::TEXTURE_WRAP_S, gl::TEXTURE_WRAP_T, gl::TEXTURE_WRAP_R] {
+                gl::TexParameteri(gl::TEXTURE_3D, wrap, gl::CLAMP_TO_BORDER as GLint);
+            }
+
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+
+            Self { gl_handle }
+        }
+    }
+
+    fn bind(&self) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_3D, self.gl_handle);
+        }
+    }
+}
+
+impl Drop for Texture3d {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteTextures(1, &self.gl_handle); }
+    }
+}
+
+// an rgba renderbuffer to draw into when there's no window to draw on
+pub struct Framebuffer {
+    gl_handle:  GLuint,
+    color:      GLuint,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Result<Self, Error> {
+        unsafe {
+            let mut color = mem::zeroed();
+            gl::GenRenderbuffers(1, &mut color);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, color);
+            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::RGBA8,
+                                    width as GLsizei,
+                                    height as GLsizei);
+
+            let mut gl_handle = mem::zeroed();
+            gl::GenFramebuffers(1, &mut gl_handle);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, gl_handle);
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER,
+                                        gl::COLOR_ATTACHMENT0,
+                                        gl::RENDERBUFFER,
+                                        color);
+
+            // dropped straight away if it's incomplete
+            let framebuffer = Self { gl_handle, color };
+
+            if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+                return Err("offscreen framebuffer is incomplete".into());
+            }
+
+            Ok(framebuffer)
+        }
+    }
+
+    pub fn bind(&self) {
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, self.gl_handle); }
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.gl_handle);
+            gl::DeleteRenderbuffers(1, &self.color);
+        }
+    }
+}
+
 unsafe fn load_opengl(gl_display: &Display) {
     gl::load_with(|sym| {
         let sym = CString::new(sym)
@@ -260,13 +571,144 @@ fn get_gl_env(iden: GLenum) -> Option<&'static str> {
     }
 }
 
+// what each pixel shows; the values match the shader's MODE_ constants
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderMode {
+    // the wave function summed along each ray, squared, so opposite phases
+    // cancel out
+    Projection = 0,
+    // the lobes where it reaches +iso and -iso, coloured by phase
+    Isosurface = 1,
+    // psi squared absorbing and emitting along each ray, coloured by phase
+    #[default]
+    Volume = 2,
+}
+
+// how volumes are read between grid points; cubic takes eight fetches to
+// linear's one but hides the grid's facets in isosurfaces
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter {
+    #[default]
+    Linear,
+    Cubic,
+}
+
+// evaluating each mo onto a grid once it's picked, rather than at every ray
+// step; only the gpu does this, the cpu always evaluates the aos
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Precompute {
+    pub enabled:    bool,
+    // in bohr, like padding, which goes around the atoms on every side
+    pub spacing:    f64,
+    pub padding:    f64,
+    // stores analytic gradients alongside, for smoother isosurface shading
+    pub gradients:  bool,
+}
+
+impl Default for Precompute {
+    fn default() -> Self {
+        Self {
+            enabled:    false,
+            spacing:    0.15,
+            padding:    4.0,
+            gradients:  false,
+        }
+    }
+}
+
+// skipping the aos too far from a sample to add more than tolerance to
+// any mo there, by sorting them into cells ahead of time
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Culling {
+    pub enabled:    bool,
+    pub tolerance:  f64,
+    // in bohr, and grown if there would be too many cells
+    pub cell_len:   f64,
+}
+
+impl Default for Culling {
+    fn default() -> Self {
+        Self {
+            enabled:    true,
+            tolerance:  1e-6,
+            cell_len:   2.0,
+        }
+    }
+}
+
+impl RenderMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Volume => Self::Isosurface,
+            Self::Isosurface => Self::Projection,
+            Self::Projection => Self::Volume,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub step_len:   f32,
+    pub num_steps:  u32,
+    // only the starting mode, isovalue and density, all of which can change
+    // in the window
+    pub mode:       RenderMode,
+    pub iso:        f32,
+    // opacity per unit of psi squared per bohr in volume mode
+    pub density:    f32,
+    pub filter:     Filter,
+    pub precompute: Precompute,
+    pub culling:    Culling,
+    pub bindings:   Bindings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            step_len:   0.1,
+            num_steps:  200,
+            mode:       RenderMode::Volume,
+            iso:        0.05,
+            density:    40.0,
+            filter:     Filter::Linear,
+            precompute: Precompute::default(),
+            culling:    Culling::default(),
+            bindings:   Bindings::default(),
+        }
+    }
+}
+
+// anything that draws an mo's image, on the gpu or off it
+pub trait Renderer {
+    fn draw_mo(&mut self, mo_idx: usize);
+    fn resize(&mut self, width: u32, height: u32);
+    fn set_camera(&mut self, camera: &Camera);
+    // rgba rows from the top down, the way images are stored
+    fn read_pixels(&self) -> Vec<u8>;
+}
+
 pub struct Drawer {
     program:    Program,
     buffer:     VertexBuffer<[f32; 2]>,
+    // aos and mo coefficients for everything but volumes, which have
+    // textures instead
+    storage:    Vec<StorageBuffer>,
+    textures:   Vec<Texture3d>,
+    precomputer: Option<Precomputer>,
+    camera:     Camera,
+    size:       (u32, u32),
+    mode:       RenderMode,
+    iso:        f32,
+    density:    f32,
 }
 
 impl Drawer {
-    pub fn new(gl_display: &Display, params: &Params) -> Self {
+    pub fn new(gl_display: &Display, params: &Params) -> Result<Self, Error> {
         #[rustfmt::skip]
         static VERTEX_DATA: [[f32; 2]; 4] = [
             [ -1.0, -1.0, ],
@@ -295,8 +737,7 @@ impl Drawer {
                                      ptr::null());
         }
 
-        let program = Program::new(params)
-                              .unwrap();
+        let program = Program::new(Program::defines(params))?;
         let layout = VertexLayout::builder()
                                   .attr(0, 2, gl::FLOAT, false)
                                   .build();
@@ -311,12 +752,181 @@ impl Drawer {
             gl::EnableVertexAttribArray(0);
         }*/
 
-        Self { program, buffer }
+        let mut drawer = Self {
+            program,
+            buffer,
+            storage:    Vec::new(),
+            textures:   Vec::new(),
+            precomputer: None,
+            camera:     Camera::default(),
+            size:       (0, 0),
+            mode:       params.settings.mode,
+            iso:        params.settings.iso,
+            density:    params.settings.density,
+        };
+
+        drawer.load(params)?;
+
+        Ok(drawer)
+    }
+
+    // uploads another input in place of the current one, which only needs a
+    // new program when it has a different kind of orbitals
+    pub fn load(&mut self, params: &Params) -> Result<(), Error> {
+        let defines = Program::defines(params);
+
+        if defines != self.program.defines {
+            self.program = Program::new(defines)?;
+        }
+
+        let num_aos = match &params.orbitals {
+            Orbitals::Contracted(bases) => {
+                let (orbitals, bas_expns, bas_coefs) = buffers::orbitals(&params.atoms, bases);
+
+                self.storage = vec![
+                    StorageBuffer::from_slice(buffers::AOS_BINDING, &orbitals),
+                    StorageBuffer::from_slice(buffers::BAS_EXPNS_BINDING, &bas_expns),
+                    StorageBuffer::from_slice(buffers::BAS_COEFS_BINDING, &bas_coefs),
+                ];
+
+                orbitals.len()
+            },
+            Orbitals::Primitive(prims) => {
+                let primitives = buffers::primitives(&params.atoms, prims);
+
+                self.storage = vec![StorageBuffer::from_slice(buffers::AOS_BINDING, &primitives)];
+
+                primitives.len()
+            },
+            Orbitals::Volume(_) => {
+                self.storage.clear();
+
+                0
+            },
+        };
+
+        if num_aos != params.mo_coefs.ncols() {
+            return Err("mo coefficients do not match the basis".into());
+        }
+
+        let settings = &params.settings;
+        let culling = &settings.culling;
+
+        if !culling.tolerance.is_finite() || culling.tolerance <= 0.0 {
+            return Err("the culling tolerance must be positive".into());
+        } else if !culling.cell_len.is_finite() || culling.cell_len <= 0.0 {
+            return Err("the culling cell length must be positive".into());
+        }
+
+        let extents = match &params.orbitals {
+            _ if !culling.enabled => None,
+            Orbitals::Contracted(bases) => {
+                Some(buffers::orbital_extents(&params.atoms, bases, &params.mo_coefs, culling.tolerance))
+            },
+            Orbitals::Primitive(prims) => {
+                Some(buffers::primitive_extents(&params.atoms, prims, &params.mo_coefs, culling.tolerance))
+            },
+            Orbitals::Volume(_) => None,
+        };
+
+        if let Some(extents) = extents {
+            let (grid, ranges, aos) = buffers::cells(&extents, culling.cell_len);
+
+            self.storage.extend([
+                StorageBuffer::from_slice(buffers::CELL_GRID_BINDING, &[grid]),
+                StorageBuffer::from_slice(buffers::CELL_RANGES_BINDING, &ranges),
+                StorageBuffer::from_slice(buffers::CELL_AOS_BINDING, &aos),
+            ]);
+        }
+
+        let precompute = &settings.precompute;
+        let (width, height) = self.size;
+
+        let precompute_grid = match &params.orbitals {
+            Orbitals::Volume(_) => None,
+            _ if precompute.enabled => Some(Grid::around(&params.atoms,
+                                                         precompute.spacing,
+                                                         precompute.padding)?),
+            _ => None,
+        };
+
+        // the compute program binds itself, so it's built first
+        self.precomputer = match &precompute_grid {
+            Some(grid) => Some(Precomputer::new(params, grid)?),
+            None => None,
+        };
+
+        // uniforms only go to the bound program
+        self.program.bind();
+        self.program.set_steps(settings.step_len, settings.num_steps);
+        self.program.set_resolution(width as f32, height as f32);
+
+        if let Orbitals::Volume(volume) = &params.orbitals {
+            self.program.set_volume(&volume.grid)?;
+            self.textures = volume.values.outer_iter()
+                                         .map(|values| Texture3d::from_array(&values))
+                                         .collect();
+        } else {
+            let mo_coefs = buffers::mo_coefs(&params.mo_coefs);
+
+            self.storage.push(StorageBuffer::from_slice(buffers::MO_COEFS_BINDING, &mo_coefs));
+            self.textures.clear();
+
+            if let Some(grid) = &precompute_grid {
+                self.program.set_volume(grid)?;
+            }
+        }
+
+        self.camera = Camera::fitted(&params.atoms);
+
+        Ok(())
     }
 
-    pub fn draw_mo(&self, mo_idx: usize) {
+    pub fn mode(&self) -> RenderMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: RenderMode) {
+        self.mode = mode;
+    }
+
+    pub fn iso(&self) -> f32 {
+        self.iso
+    }
+
+    pub fn set_iso(&mut self, iso: f32) {
+        self.iso = iso;
+    }
+
+    pub fn density(&self) -> f32 {
+        self.density
+    }
+
+    pub fn set_density(&mut self, density: f32) {
+        self.density = density;
+    }
+}
+
+impl Renderer for Drawer {
+    fn draw_mo(&mut self, mo_idx: usize) {
+        for buffer in &self.storage {
+            buffer.bind();
+        }
+
+        // the compute shader has its own program, so it goes first
+        if let Some(precomputer) = &mut self.precomputer {
+            precomputer.fill(mo_idx);
+            precomputer.texture.bind();
+        } else if let Some(texture) = self.textures.get(mo_idx) {
+            texture.bind();
+        }
+
         self.program.bind();
         self.program.set_mo_idx(mo_idx as i32);
+        self.program.set_mode(self.mode);
+        self.program.set_iso(self.iso);
+        self.program.set_density(self.density);
+        self.program.set_camera(&self.camera);
 
         unsafe {
             gl::ClearColor(0.9, 0.9, 0.9, 1.0);
@@ -326,12 +936,43 @@ impl Drawer {
         self.buffer.draw(gl::TRIANGLE_STRIP);
     }
 
-    pub fn resize(&self, width: u32, height: u32) {
+    fn resize(&mut self, width: u32, height: u32) {
+        self.size = (width, height);
+
         let (width, height) = (width as i32, height as i32);
         unsafe { gl::Viewport(0, 0, width, height); }
 
+        // uniforms only go to the bound program
+        self.program.bind();
         self.program.set_resolution(width as f32, height as f32);
     }
+
+    fn set_camera(&mut self, camera: &Camera) {
+        self.camera = camera.clone();
+    }
+
+    // from whatever framebuffer is bound, the window's or an offscreen one
+    fn read_pixels(&self) -> Vec<u8> {
+        let (width, height) = self.size;
+        let row_len = 4 * width as usize;
+        let mut pixels = vec![0u8; row_len * height as usize];
+
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(0, 0,
+                           width as GLsizei,
+                           height as GLsizei,
+                           gl::RGBA, gl::UNSIGNED_BYTE,
+                           pixels.as_mut_ptr() as *mut _);
+        }
+
+        // gl reads from the bottom row up
+        pixels.chunks_exact(row_len)
+              .rev()
+              .flatten()
+              .copied()
+              .collect()
+    }
 }
 
 extern "system" fn gl_debug_callback(source:        GLenum,
@@ -0,0 +1,114 @@
+use crate::{
+    cube::Grid,
+    error::Error,
+    gl::{ self, types::* },
+    Params,
+};
+use super::{ aos_defines, expand, Shader, TexelFormat, Texture3d };
+
+// 64 mb of values, or four times that with gradients
+const MAX_POINTS: usize = 1 << 24;
+// in step with compute.glsl's local size
+const GROUP_SIZE: usize = 4;
+
+struct ComputeProgram {
+    gl_handle:  GLuint,
+    mo_idx:     GLint,
+}
+
+impl ComputeProgram {
+    fn new(defines: &str, grid: &Grid) -> Result<Self, Error> {
+        const COMPUTE_TEMPLATE: &str = include_str!("compute.glsl");
+
+        let shader = Shader::new(gl::COMPUTE_SHADER, &expand(COMPUTE_TEMPLATE, defines))?;
+        let org = grid.origin.map(|x| x as f32);
+        let axes = grid.axes.map(|axis| axis.map(|x| x as f32));
+
+        unsafe {
+            let gl_handle = gl::CreateProgram();
+            gl::AttachShader(gl_handle, shader.gl_handle);
+            gl::LinkProgram(gl_handle);
+
+            let mo_idx = gl::GetUniformLocation(gl_handle, c_str!("mo_idx"));
+            let vol_org = gl::GetUniformLocation(gl_handle, c_str!("vol_org"));
+            let vol_axes = gl::GetUniformLocation(gl_handle, c_str!("vol_axes"));
+
+            // the grid stays put for as long as the program does
+            gl::UseProgram(gl_handle);
+            gl::Uniform3fv(vol_org, 1, org.as_ptr());
+            gl::UniformMatrix3fv(vol_axes, 1, gl::FALSE, axes.as_ptr() as *const _);
+
+            Ok(Self { gl_handle, mo_idx })
+        }
+    }
+}
+
+impl Drop for ComputeProgram {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteProgram(self.gl_handle); }
+    }
+}
+
+// evaluates one mo onto a grid whenever the mo changes, so the ray marcher
+// can sample it like a volume instead of summing every ao at every step
+pub(super) struct Precomputer {
+    program:    ComputeProgram,
+    shape:      [usize; 3],
+    format:     TexelFormat,
+    pub texture: Texture3d,
+    // whichever mo the texture holds
+    mo_idx:     Option<usize>,
+}
+
+impl Precomputer {
+    pub fn new(params: &Params, grid: &Grid) -> Result<Self, Error> {
+        let precompute = &params.settings.precompute;
+
+        if grid.shape.iter().product::<usize>() > MAX_POINTS {
+            return Err("the precompute grid has too many points, try a larger spacing".into());
+        }
+
+        let mut defines = aos_defines(params);
+
+        let format = if precompute.gradients {
+            defines.push("#define VOLUME_GRADIENTS");
+
+            TexelFormat::Rgba32f
+        } else {
+            TexelFormat::R32f
+        };
+
+        let [nx, ny, nz] = grid.shape;
+
+        Ok(Self {
+            program:    ComputeProgram::new(&defines.join("\n"), grid)?,
+            shape:      grid.shape,
+            format,
+            texture:    Texture3d::empty((nx, ny, nz), format),
+            mo_idx:     None,
+        })
+    }
+
+    // the aos' storage buffers have to be bound already
+    pub fn fill(&mut self, mo_idx: usize) {
+        if self.mo_idx == Some(mo_idx) {
+            return;
+        }
+
+        // one invocation per texel, so the grid's axes go in backwards
+        let groups = self.shape.map(|n| n.div_ceil(GROUP_SIZE) as GLuint);
+
+        unsafe {
+            gl::UseProgram(self.program.gl_handle);
+            gl::Uniform1i(self.program.mo_idx, mo_idx as i32);
+            gl::BindImageTexture(0, self.texture.gl_handle, 0, gl::TRUE, 0,
+                                 gl::WRITE_ONLY, self.format.internal());
+            gl::DispatchCompute(groups[2], groups[1], groups[0]);
+            // sampling has to wait for every write
+            gl::MemoryBarrier(gl::TEXTURE_FETCH_BARRIER_BIT);
+        }
+
+        self.mo_idx = Some(mo_idx);
+    }
+}
+
@@ -0,0 +1,32 @@
+// indexed by atomic number, so the first entry is a placeholder
+const SYMBOLS: [&str; 119] = [
+    "X",
+    "H", "He",
+    "Li", "Be", "B", "C", "N", "O", "F", "Ne",
+    "Na", "Mg", "Al", "Si", "P", "S", "Cl", "Ar",
+    "K", "Ca", "Sc", "Ti", "V", "Cr", "Mn", "Fe", "Co", "Ni", "Cu", "Zn",
+    "Ga", "Ge", "As", "Se", "Br", "Kr",
+    "Rb", "Sr", "Y", "Zr", "Nb", "Mo", "Tc", "Ru", "Rh", "Pd", "Ag", "Cd",
+    "In", "Sn", "Sb", "Te", "I", "Xe",
+    "Cs", "Ba", "La", "Ce", "Pr", "Nd", "Pm", "Sm", "Eu", "Gd", "Tb", "Dy",
+    "Ho", "Er", "Tm", "Yb", "Lu", "Hf", "Ta", "W", "Re", "Os", "Ir", "Pt",
+    "Au", "Hg", "Tl", "Pb", "Bi", "Po", "At", "Rn",
+    "Fr", "Ra", "Ac", "Th", "Pa", "U", "Np", "Pu", "Am", "Cm", "Bk", "Cf",
+    "Es", "Fm", "Md", "No", "Lr", "Rf", "Db", "Sg", "Bh", "Hs", "Mt", "Ds",
+    "Rg", "Cn", "Nh", "Fl", "Mc", "Lv", "Ts", "Og",
+];
+
+pub fn symbol(atomic_num: u32) -> &'static str {
+    SYMBOLS.get(atomic_num as usize)
+           .unwrap_or(&SYMBOLS[0])
+}
+
+// deuterium and tritium show up as their own symbols in structure files
+pub fn atomic_num(symbol: &str) -> Option<u32> {
+    match symbol {
+        "D" | "T" => Some(1),
+        _ => SYMBOLS[1..].iter()
+                         .position(|s| s.eq_ignore_ascii_case(symbol))
+                         .map(|i| i as u32 + 1),
+    }
+}
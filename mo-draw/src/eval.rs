@@ -0,0 +1,355 @@
+use std::thread;
+use ndarray::{
+    Array, Array2, Array3, ArrayView2, ArrayViewMut, Axis, Dimension,
+};
+use crate::{
+    error::Error,
+    harmonics::{ self, Term },
+    Orbitals, Params,
+};
+
+// how many points get their aos laid out at once before the mo product
+const BATCH_LEN: usize = 256;
+
+// one ao placed on its atom, the same way the fragment shader sees it:
+// primitive norms are already in the coefficients
+#[derive(Debug, Clone)]
+pub struct Ao {
+    pub org:    [f64; 3],
+    // the solid harmonic for this ao's m, or its cartesian component
+    pub ang:    Vec<Term>,
+    pub expns:  Vec<f64>,
+    pub coefs:  Vec<f64>,
+}
+
+impl Ao {
+    pub fn value(&self, pos: [f64; 3]) -> f64 {
+        let pos = [0, 1, 2].map(|i| pos[i] - self.org[i]);
+        let r2 = pos.iter().map(|x| x * x).sum::<f64>();
+        let radial = self.expns.iter()
+                               .zip(&self.coefs)
+                               .map(|(expn, coef)| coef * (-expn * r2).exp())
+                               .sum::<f64>();
+
+        radial * harmonics::eval(&self.ang, pos)
+    }
+
+    pub fn gradient(&self, pos: [f64; 3]) -> [f64; 3] {
+        let pos = [0, 1, 2].map(|i| pos[i] - self.org[i]);
+        let r2 = pos.iter().map(|x| x * x).sum::<f64>();
+        let mut radial = 0.0;
+        // the radial part's gradient is this times pos
+        let mut d_radial = 0.0;
+
+        for (expn, coef) in self.expns.iter().zip(&self.coefs) {
+            let g = coef * (-expn * r2).exp();
+
+            radial += g;
+            d_radial -= 2.0 * expn * g;
+        }
+
+        let ang = harmonics::eval(&self.ang, pos);
+        let d_ang = harmonics::gradient(&self.ang, pos);
+
+        [0, 1, 2].map(|i| d_radial * pos[i] * ang + radial * d_ang[i])
+    }
+}
+
+// every ao of the input, in mo coefficient order
+pub struct Evaluator {
+    pub aos:    Vec<Ao>,
+}
+
+impl Evaluator {
+    pub fn new(params: &Params) -> Result<Self, Error> {
+        let mut aos = Vec::new();
+
+        match &params.orbitals {
+            Orbitals::Contracted(bases) => {
+                for atom in &params.atoms {
+                    let basis = bases.get(atom.basis_id)
+                                     .ok_or("atom refers to a missing basis")?;
+
+                    for shell in &basis.shells {
+                        let l = shell.l;
+                        let angs = if shell.cart {
+                            harmonics::cart_pows(l).into_iter()
+                                                   .map(|pows| vec![harmonics::cartesian(pows)])
+                                                   .collect()
+                        } else {
+                            (-l..=l).map(|m| harmonics::solid_harmonic(l, m))
+                                    .collect::<Vec<_>>()
+                        };
+
+                        for ang in angs {
+                            aos.push(Ao {
+                                org:    atom.position,
+                                ang,
+                                expns:  shell.expns.clone(),
+                                coefs:  shell.norm_coefs(),
+                            });
+                        }
+                    }
+                }
+            },
+            // primitive inputs already fold any normalization into their
+            // mo coefficients
+            Orbitals::Primitive(prims) => {
+                for prim in prims {
+                    let atom = params.atoms.get(prim.atom_idx)
+                                           .ok_or("primitive refers to a missing atom")?;
+
+                    aos.push(Ao {
+                        org:    atom.position,
+                        ang:    vec![Term { coef: 1.0, pows: prim.pows }],
+                        expns:  vec![prim.expn],
+                        coefs:  vec![1.0],
+                    });
+                }
+            },
+            Orbitals::Volume(_) => return Err("volume inputs have no aos to evaluate".into()),
+        }
+
+        Ok(Self { aos })
+    }
+
+    // points are one per row, and so is every result
+    pub fn aos(&self, points: ArrayView2<f64>) -> Array2<f64> {
+        let mut out = Array2::zeros((points.nrows(), self.aos.len()));
+
+        par_rows(points, &mut out, |points, mut out| {
+            for (pos, mut row) in points.rows().into_iter().zip(out.rows_mut()) {
+                let pos = [pos[0], pos[1], pos[2]];
+
+                for (ao, value) in self.aos.iter().zip(row.iter_mut()) {
+                    *value = ao.value(pos);
+                }
+            }
+        });
+
+        out
+    }
+
+    // indexed by point, then ao, then axis
+    pub fn ao_gradients(&self, points: ArrayView2<f64>) -> Array3<f64> {
+        let mut out = Array3::zeros((points.nrows(), self.aos.len(), 3));
+
+        par_rows(points, &mut out, |points, mut out| {
+            for (pos, mut grads) in points.rows().into_iter().zip(out.outer_iter_mut()) {
+                let pos = [pos[0], pos[1], pos[2]];
+
+                for (ao, mut grad) in self.aos.iter().zip(grads.rows_mut()) {
+                    grad.assign(&ndarray::arr1(&ao.gradient(pos)));
+                }
+            }
+        });
+
+        out
+    }
+
+    // mo_coefs has one mo per row, like Params; the result one per column
+    pub fn mos(&self,
+               mo_coefs:    ArrayView2<f64>,
+               points:      ArrayView2<f64>) -> Result<Array2<f64>, Error> {
+        self.check_coefs(mo_coefs)?;
+
+        let mut out = Array2::zeros((points.nrows(), mo_coefs.nrows()));
+
+        par_rows(points, &mut out, |points, mut out| {
+            let batches = points.axis_chunks_iter(Axis(0), BATCH_LEN)
+                                .zip(out.axis_chunks_iter_mut(Axis(0), BATCH_LEN));
+
+            for (points, mut out) in batches {
+                out.assign(&self.aos(points).dot(&mo_coefs.t()));
+            }
+        });
+
+        Ok(out)
+    }
+
+    // indexed by point, then mo, then axis
+    pub fn mo_gradients(&self,
+                        mo_coefs:   ArrayView2<f64>,
+                        points:     ArrayView2<f64>) -> Result<Array3<f64>, Error> {
+        self.check_coefs(mo_coefs)?;
+
+        let mut out = Array3::zeros((points.nrows(), mo_coefs.nrows(), 3));
+
+        par_rows(points, &mut out, |points, mut out| {
+            let batches = points.axis_chunks_iter(Axis(0), BATCH_LEN)
+                                .zip(out.axis_chunks_iter_mut(Axis(0), BATCH_LEN));
+
+            for (points, mut out) in batches {
+                let grads = self.ao_gradients(points);
+
+                for axis in 0..3 {
+                    out.index_axis_mut(Axis(2), axis)
+                       .assign(&grads.index_axis(Axis(2), axis).dot(&mo_coefs.t()));
+                }
+            }
+        });
+
+        Ok(out)
+    }
+
+    fn check_coefs(&self, mo_coefs: ArrayView2<f64>) -> Result<(), Error> {
+        if mo_coefs.ncols() != self.aos.len() {
+            return Err("mo coefficients do not match the basis".into());
+        }
+
+        Ok(())
+    }
+}
+
+// splits the points evenly across threads, each filling in its own rows of
+// out; anything up to a batch long stays on the calling thread
+fn par_rows<D, F>(points: ArrayView2<f64>, out: &mut Array<f64, D>, f: F)
+where
+    D: Dimension,
+    F: Fn(ArrayView2<f64>, ArrayViewMut<f64, D>) + Sync,
+{
+    let num_threads = thread::available_parallelism()
+                             .map_or(1, |n| n.get());
+    let chunk_len = points.nrows()
+                          .div_ceil(num_threads)
+                          .max(BATCH_LEN);
+
+    if points.nrows() <= chunk_len {
+        return f(points, out.view_mut());
+    }
+
+    let f = &f;
+
+    thread::scope(|scope| {
+        let chunks = points.axis_chunks_iter(Axis(0), chunk_len)
+                           .zip(out.axis_chunks_iter_mut(Axis(0), chunk_len));
+
+        for (points, out) in chunks {
+            scope.spawn(move || f(points, out));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{ array, Array1 };
+    use crate::{
+        atom::Atom,
+        basis::{ Basis, Shell },
+        drawer::Settings,
+    };
+    use super::*;
+
+    // s, p, spherical d with a two primitive contraction, and cartesian d
+    fn evaluator(org: [f64; 3]) -> Evaluator {
+        let mut d = Shell { l: 2, cart: false, expns: vec![1.2, 0.4], coefs: vec![0.6, 0.5] };
+        let norm = d.contraction_norm().sqrt();
+
+        d.coefs.iter_mut().for_each(|c| *c /= norm);
+
+        let shells = vec![
+            Shell { l: 0, cart: false, expns: vec![0.8], coefs: vec![1.0] },
+            Shell { l: 1, cart: false, expns: vec![0.7], coefs: vec![1.0] },
+            d,
+            Shell { l: 2, cart: true, expns: vec![0.9], coefs: vec![1.0] },
+        ];
+        let params = Params {
+            atoms:      array![Atom { basis_id: 0, atomic_num: 1, position: org }],
+            orbitals:   Orbitals::Contracted(array![Basis { shells }]),
+            mo_coefs:   Array2::zeros((0, 0)),
+            mo_info:    Array1::from_vec(Vec::new()),
+            settings:   Settings::default(),
+        };
+
+        Evaluator::new(&params).unwrap()
+    }
+
+    // a cube of side 2 * half_len, one point per row
+    fn cube_points(half_len: f64, spacing: f64) -> Array2<f64> {
+        let n = (2.0 * half_len / spacing).round() as usize + 1;
+
+        Array2::from_shape_fn((n * n * n, 3), |(idx, i)| {
+            let k = [idx / (n * n), idx / n % n, idx % n][i];
+
+            k as f64 * spacing - half_len
+        })
+    }
+
+    // scattered, but the same every run
+    fn scattered_points(len: usize) -> Array2<f64> {
+        Array2::from_shape_fn((len, 3), |(row, i)| {
+            4.0 * ((row * 3 + i) as f64 * 0.618034).fract() - 2.0
+        })
+    }
+
+    #[test]
+    fn aos_are_normalized() {
+        let evaluator = evaluator([0.0; 3]);
+        let spacing = 0.2;
+        let aos = evaluator.aos(cube_points(7.0, spacing).view());
+
+        assert_eq!(aos.ncols(), 1 + 3 + 5 + 6);
+
+        for (ao_idx, values) in aos.columns().into_iter().enumerate() {
+            let norm = values.iter().map(|x| x * x).sum::<f64>() * spacing.powi(3);
+
+            assert!((norm - 1.0).abs() < 1e-6, "ao {ao_idx} has norm {norm}");
+        }
+    }
+
+    #[test]
+    fn gradients_match_central_differences() {
+        let evaluator = evaluator([0.3, -0.2, 0.5]);
+        let h = 1e-5;
+
+        for pos in scattered_points(20).rows() {
+            let pos = [pos[0], pos[1], pos[2]];
+
+            for ao in &evaluator.aos {
+                let gradient = ao.gradient(pos);
+
+                for i in 0..3 {
+                    let (mut fwd, mut back) = (pos, pos);
+
+                    fwd[i] += h;
+                    back[i] -= h;
+
+                    let diff = (ao.value(fwd) - ao.value(back)) / (2.0 * h);
+
+                    assert!((gradient[i] - diff).abs() < 1e-7,
+                            "d/d{i} is {} but differences give {diff} at {pos:?}", gradient[i]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mos_match_across_threads() {
+        let evaluator = evaluator([0.0; 3]);
+        let num_threads = thread::available_parallelism()
+                                 .map_or(1, |n| n.get());
+        // enough to split across every thread, and not evenly
+        let points = scattered_points(BATCH_LEN * (num_threads + 1) + 37);
+        let num_aos = evaluator.aos.len();
+        let mo_coefs = Array2::from_shape_fn((4, num_aos), |(i, j)| ((i * num_aos + j) as f64).sin());
+
+        let mos = evaluator.mos(mo_coefs.view(), points.view())
+                           .unwrap();
+        let expected = evaluator.aos(points.view())
+                                .dot(&mo_coefs.t());
+
+        assert_eq!(mos.dim(), (points.nrows(), 4));
+
+        for (mo, expected) in mos.iter().zip(&expected) {
+            assert!((mo - expected).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn mos_check_the_basis() {
+        let evaluator = evaluator([0.0; 3]);
+        let mo_coefs = Array2::zeros((1, 2));
+
+        assert!(evaluator.mos(mo_coefs.view(), scattered_points(4).view()).is_err());
+    }
+}
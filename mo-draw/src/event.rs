@@ -0,0 +1,121 @@
+use std::time::Instant;
+use winit::event::{
+    ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta,
+    WindowEvent,
+};
+
+// the time between drawn frames, for anything that moves while a key is
+// held rather than once per press
+#[derive(Debug, Clone, Copy)]
+pub struct Frame {
+    pub time:   Instant,
+    // seconds since the frame before
+    pub dt:     f64,
+}
+
+impl Frame {
+    pub fn initial() -> Self {
+        Self { time: Instant::now(), dt: 0.0 }
+    }
+
+    pub fn next(&mut self) {
+        let now = Instant::now();
+
+        self.dt = (now - self.time).as_secs_f64();
+        self.time = now;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    Left,
+    Right,
+    Middle,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scroll {
+    // a mouse wheel's notches, up being positive
+    Lines(f64),
+    // a touchpad's two finger scroll, in physical pixels
+    Pixels(f64, f64),
+}
+
+// everything the controller reacts to, without any of winit's types so it
+// can be driven by hand as well as by a window
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    // keys go by winit's names for them, like "Tab", "Right" or "Key1"
+    Key { name: String, pressed: bool },
+    Modifiers { shift: bool, ctrl: bool },
+    Button { button: Button, pressed: bool },
+    // in physical pixels from the top left
+    CursorMoved { x: f64, y: f64 },
+    CursorLeft,
+    Scroll(Scroll),
+    Resized { width: u32, height: u32 },
+    CloseRequested,
+    // once per drawn frame
+    Tick,
+}
+
+impl Input {
+    pub fn from_winit<T>(evt: &Event<T>) -> Option<Self> {
+        let event = match evt {
+            Event::WindowEvent { event, .. } => event,
+            Event::RedrawEventsCleared => return Some(Self::Tick),
+            _ => return None,
+        };
+
+        let input = match event {
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput { state, virtual_keycode: Some(key), .. },
+                ..
+            } => {
+                Self::Key {
+                    name:       format!("{key:?}"),
+                    pressed:    *state == ElementState::Pressed,
+                }
+            },
+            WindowEvent::ModifiersChanged(state) => {
+                Self::Modifiers { shift: state.shift(), ctrl: state.ctrl() }
+            },
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    MouseButton::Left => Button::Left,
+                    MouseButton::Right => Button::Right,
+                    MouseButton::Middle => Button::Middle,
+                    MouseButton::Other(_) => Button::Other,
+                };
+
+                Self::Button { button, pressed: *state == ElementState::Pressed }
+            },
+            WindowEvent::CursorMoved { position, .. } => {
+                Self::CursorMoved { x: position.x, y: position.y }
+            },
+            WindowEvent::CursorLeft { .. } => Self::CursorLeft,
+            WindowEvent::MouseWheel { delta, .. } => match delta {
+                MouseScrollDelta::LineDelta(_, y) => Self::Scroll(Scroll::Lines(*y as f64)),
+                MouseScrollDelta::PixelDelta(pos) => Self::Scroll(Scroll::Pixels(pos.x, pos.y)),
+            },
+            WindowEvent::Resized(size) => {
+                Self::Resized { width: size.width, height: size.height }
+            },
+            WindowEvent::CloseRequested => Self::CloseRequested,
+            _ => return None,
+        };
+
+        Some(input)
+    }
+}
+
+pub trait HandleEvent {
+    fn handle_input(&mut self, frame: &Frame, input: &Input);
+
+    fn handle_event<T>(&mut self, frame: &Frame, evt: &Event<T>) {
+        if let Some(input) = Input::from_winit(evt) {
+            self.handle_input(frame, &input);
+        }
+    }
+}
@@ -0,0 +1,395 @@
+use std::collections::HashMap;
+use ndarray::{ concatenate, Array1, Array2, Axis };
+use crate::{
+    atom::Atom,
+    basis::{ Basis, Shell, ShellNorm },
+    error::Error,
+    mo_coefs::{ MoInfo, Spin },
+    Orbitals, Params,
+};
+
+enum Value {
+    Int(Vec<i64>),
+    Real(Vec<f64>),
+    Other,
+}
+
+struct Fchk {
+    values: HashMap<String, Value>,
+}
+
+impl Fchk {
+    fn parse(text: &str) -> Result<Self, Error> {
+        let mut values = HashMap::new();
+        // the first two lines are the title and the job type
+        let mut lines = text.lines()
+                            .skip(2)
+                            .peekable();
+
+        while let Some(line) = lines.next() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            if line.len() < 44 || !line.is_char_boundary(43) {
+                return Err("invalid fchk line".into());
+            }
+
+            let name = line[..40].trim()
+                                 .to_string();
+            let ty = &line[43..44];
+            let rest = line[44..].trim();
+
+            let value = match rest.strip_prefix("N=") {
+                Some(len) => {
+                    let len: usize = len.trim().parse()?;
+                    let mut tokens = Vec::new();
+
+                    while let Some(next) = lines.peek() {
+                        if is_key_line(next) {
+                            break;
+                        }
+
+                        tokens.extend(next.split_whitespace());
+                        lines.next();
+                    }
+
+                    match ty {
+                        "I" => Value::Int(parse_tokens(&tokens, len)?),
+                        "R" => Value::Real(parse_tokens(&tokens, len)?),
+                        _ => Value::Other,
+                    }
+                },
+                None => match ty {
+                    "I" => Value::Int(vec![rest.parse()?]),
+                    "R" => Value::Real(vec![rest.parse()?]),
+                    _ => Value::Other,
+                },
+            };
+
+            values.insert(name, value);
+        }
+
+        Ok(Self { values })
+    }
+
+    fn ints(&self, name: &str) -> Result<&[i64], Error> {
+        match self.values.get(name) {
+            Some(Value::Int(v)) => Ok(v),
+            _ => Err(format!("fchk file has no integer field '{name}'").into()),
+        }
+    }
+
+    fn int(&self, name: &str) -> Result<i64, Error> {
+        Ok(self.ints(name)?[0])
+    }
+
+    fn reals(&self, name: &str) -> Result<&[f64], Error> {
+        match self.values.get(name) {
+            Some(Value::Real(v)) => Ok(v),
+            _ => Err(format!("fchk file has no real field '{name}'").into()),
+        }
+    }
+}
+
+fn is_key_line(line: &str) -> bool {
+    line.len() >= 44
+        && line.starts_with(|c: char| c.is_ascii_alphabetic())
+        && matches!(line.get(43..44), Some("I" | "R" | "C" | "H" | "L"))
+}
+
+fn parse_tokens<T>(tokens: &[&str], len: usize) -> Result<Vec<T>, Error>
+where
+    T: std::str::FromStr,
+    T::Err: std::error::Error + 'static,
+{
+    if tokens.len() != len {
+        return Err("fchk array has the wrong length".into());
+    }
+
+    let mut v = Vec::with_capacity(len);
+
+    for token in tokens {
+        v.push(token.parse()?);
+    }
+
+    Ok(v)
+}
+
+fn read_shells(fchk: &Fchk, num_atoms: usize) -> Result<Vec<Vec<Shell>>, Error> {
+    let types = fchk.ints("Shell types")?;
+    let num_prims = fchk.ints("Number of primitives per shell")?;
+    let atom_map = fchk.ints("Shell to atom map")?;
+    let expns = fchk.reals("Primitive exponents")?;
+    let coefs = fchk.reals("Contraction coefficients")?;
+    let sp_coefs = fchk.reals("P(S=P) Contraction coefficients")
+                       .unwrap_or(&[]);
+
+    if num_prims.len() != types.len() || atom_map.len() != types.len() {
+        return Err("inconsistent fchk shell arrays".into());
+    }
+
+    let mut shellses = vec![Vec::new(); num_atoms];
+    let mut offset = 0;
+
+    for i in 0..types.len() {
+        let end = offset + num_prims[i] as usize;
+
+        if end > expns.len() || end > coefs.len() {
+            return Err("fchk shells run past the primitive arrays".into());
+        }
+
+        let atom_idx = atom_map[i] as usize;
+
+        if atom_idx < 1 || atom_idx > num_atoms {
+            return Err("invalid fchk shell to atom map".into());
+        }
+
+        let shells: &mut Vec<Shell> = &mut shellses[atom_idx - 1];
+        let shell_expns = expns[offset..end].to_vec();
+        let shell_coefs = coefs[offset..end].to_vec();
+
+        // 0 and 1 are s and p, -1 is sp, and beyond that negative means pure
+        // and positive means cartesian
+        match types[i] {
+            -1 => {
+                if end > sp_coefs.len() {
+                    return Err("fchk sp shell has no p coefficients".into());
+                }
+
+                let shell_sp_coefs = sp_coefs[offset..end].to_vec();
+                shells.extend(Shell::split_sp(shell_expns, shell_coefs, shell_sp_coefs));
+            },
+            ty => shells.push(Shell {
+                l:      ty.abs() as i32,
+                cart:   ty > 1,
+                expns:  shell_expns,
+                coefs:  shell_coefs,
+            }),
+        }
+
+        offset = end;
+    }
+
+    Ok(shellses)
+}
+
+// coefficients and energies for one spin
+type Mos<'a> = (Array2<f64>, &'a [f64]);
+
+fn read_mos<'a>(fchk: &'a Fchk,
+                spin: Spin,
+                num_ao: usize) -> Result<Option<Mos<'a>>, Error> {
+    let (coefs_name, energies_name) = match spin {
+        Spin::Alpha => ("Alpha MO coefficients", "Alpha Orbital Energies"),
+        Spin::Beta => ("Beta MO coefficients", "Beta Orbital Energies"),
+    };
+
+    if !fchk.values.contains_key(coefs_name) {
+        return Ok(None);
+    }
+
+    let coefs = fchk.reals(coefs_name)?;
+    let energies = fchk.reals(energies_name)?;
+
+    if coefs.len() != energies.len() * num_ao {
+        return Err("fchk mo coefficients do not match the basis".into());
+    }
+
+    // gaussian writes one mo after another, so rows are already mos
+    let mo_coefs = Array2::from_shape_vec((energies.len(), num_ao), coefs.to_vec())?;
+
+    Ok(Some((mo_coefs, energies)))
+}
+
+pub fn parse(text: &str) -> Result<Params, Error> {
+    let fchk = Fchk::parse(text)?;
+
+    let atomic_nums = fchk.ints("Atomic numbers")?;
+    let coords = fchk.reals("Current cartesian coordinates")?;
+
+    if coords.len() != 3 * atomic_nums.len() {
+        return Err("fchk coordinates do not match the atoms".into());
+    }
+
+    // fchk coordinates are already in bohr
+    let atoms = atomic_nums.iter()
+                           .enumerate()
+                           .map(|(i, &z)| Atom {
+                               basis_id:   i,
+                               atomic_num: z as u32,
+                               position:   [coords[3 * i], coords[3 * i + 1], coords[3 * i + 2]],
+                           })
+                           .collect::<Array1<_>>();
+
+    let shellses = read_shells(&fchk, atoms.len())?;
+    let bases = shellses.iter()
+                        .map(|shells| Basis::from_shells(shells, ShellNorm::Primitive))
+                        .collect::<Result<Array1<_>, _>>()?;
+
+    let num_ao = fchk.int("Number of basis functions")? as usize;
+
+    if num_ao != shellses.iter().flatten().map(|s| s.num_aos()).sum::<usize>() {
+        return Err("fchk basis function count does not match the shells".into());
+    }
+
+    let num_alpha = fchk.int("Number of alpha electrons")? as usize;
+    let num_beta = fchk.int("Number of beta electrons")? as usize;
+
+    let alpha = read_mos(&fchk, Spin::Alpha, num_ao)?;
+    let beta = read_mos(&fchk, Spin::Beta, num_ao)?;
+    let (mut mo_coefs, energies) = alpha.ok_or("fchk file has no mo coefficients")?;
+
+    // fchk files don't store occupations, so they have to be filled in from
+    // the electron counts; restricted files only carry alpha orbitals
+    let occ = |i: usize, num_occ: usize| if i < num_occ { 1.0 } else { 0.0 };
+    let mut mo_info = energies.iter()
+                              .enumerate()
+                              .map(|(i, &energy)| MoInfo {
+                                  energy: Some(energy),
+                                  occ:    Some(match beta {
+                                      Some(_) => occ(i, num_alpha),
+                                      None => occ(i, num_alpha) + occ(i, num_beta),
+                                  }),
+                                  spin:   Spin::Alpha,
+                              })
+                              .collect::<Vec<_>>();
+
+    if let Some((beta_coefs, energies)) = beta {
+        mo_coefs = concatenate(Axis(0), &[mo_coefs.view(), beta_coefs.view()])?;
+        mo_info.extend(energies.iter()
+                               .enumerate()
+                               .map(|(i, &energy)| MoInfo {
+                                   energy: Some(energy),
+                                   occ:    Some(occ(i, num_beta)),
+                                   spin:   Spin::Beta,
+                               }));
+    }
+
+    Ok(Params {
+        atoms,
+        orbitals:   Orbitals::Contracted(bases),
+        mo_coefs,
+        mo_info:    mo_info.into(),
+        settings:   Default::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write;
+    use super::*;
+
+    fn scalar(text: &mut String, name: &str, value: i64) {
+        writeln!(text, "{name:<40}   I     {value:>12}").unwrap();
+    }
+
+    fn ints(text: &mut String, name: &str, values: &[i64]) {
+        writeln!(text, "{name:<40}   I   N={:>12}", values.len()).unwrap();
+
+        for chunk in values.chunks(6) {
+            let line = chunk.iter().map(|v| format!("{v:>12}"));
+
+            writeln!(text, "{}", line.collect::<String>()).unwrap();
+        }
+    }
+
+    fn reals(text: &mut String, name: &str, values: &[f64]) {
+        writeln!(text, "{name:<40}   R   N={:>12}", values.len()).unwrap();
+
+        for chunk in values.chunks(5) {
+            let line = chunk.iter().map(|v| format!("{v:>16.8E}"));
+
+            writeln!(text, "{}", line.collect::<String>()).unwrap();
+        }
+    }
+
+    // o with an s, an sp, a pure d and a cartesian d, and h with an s, for
+    // 1 + 4 + 5 + 6 + 1 aos
+    const NUM_AOS: usize = 17;
+
+    fn fchk(unrestricted: bool) -> String {
+        let mut text = format!("water\nSP        RHF{:>63}\n", "STO-3G");
+        let coefs = (0..2 * NUM_AOS).map(|i| 0.01 * i as f64)
+                                    .collect::<Vec<_>>();
+
+        ints(&mut text, "Atomic numbers", &[8, 1]);
+        reals(&mut text, "Current cartesian coordinates", &[0.0, 0.0, 0.0, 0.0, 0.0, 1.8]);
+        scalar(&mut text, "Number of alpha electrons", 1);
+        scalar(&mut text, "Number of beta electrons", 1);
+        scalar(&mut text, "Number of basis functions", NUM_AOS as i64);
+        ints(&mut text, "Shell types", &[0, -1, -2, 2, 0]);
+        ints(&mut text, "Number of primitives per shell", &[1, 2, 1, 1, 1]);
+        ints(&mut text, "Shell to atom map", &[1, 1, 1, 1, 2]);
+        reals(&mut text, "Primitive exponents", &[130.7, 5.03, 1.17, 0.8, 0.9, 3.43]);
+        reals(&mut text, "Contraction coefficients", &[1.0, -0.1, 0.4, 1.0, 1.0, 1.0]);
+        reals(&mut text, "P(S=P) Contraction coefficients", &[0.0, 0.16, 0.61, 0.0, 0.0, 0.0]);
+        reals(&mut text, "Alpha Orbital Energies", &[-20.25, 0.6]);
+        reals(&mut text, "Alpha MO coefficients", &coefs);
+
+        if unrestricted {
+            reals(&mut text, "Beta Orbital Energies", &[-20.2, 0.7]);
+            reals(&mut text, "Beta MO coefficients", &coefs);
+        }
+
+        text
+    }
+
+    #[test]
+    fn reads_shell_types() {
+        let params = parse(&fchk(false)).unwrap();
+        let Orbitals::Contracted(bases) = &params.orbitals else {
+            panic!("fchk input gave primitive orbitals");
+        };
+        let shells = bases[0].shells
+                             .iter()
+                             .map(|s| (s.l, s.cart, s.expns.len()))
+                             .collect::<Vec<_>>();
+
+        // -1 splits into an s and a p, -2 is pure and 2 cartesian
+        assert_eq!(shells,
+                   [(0, false, 1), (0, false, 2), (1, false, 2), (2, false, 1), (2, true, 1)]);
+        assert_eq!(bases[0].shells[1].coefs, [-0.1, 0.4]);
+        assert_eq!(bases[0].shells[2].coefs, [0.16, 0.61]);
+        assert_eq!(bases[1].shells.len(), 1);
+        assert_eq!(params.atoms[1].position, [0.0, 0.0, 1.8]);
+    }
+
+    #[test]
+    fn restricted_mos_are_doubly_occupied() {
+        let params = parse(&fchk(false)).unwrap();
+
+        assert_eq!(params.mo_coefs.dim(), (2, NUM_AOS));
+        // one mo after another
+        assert_eq!(params.mo_coefs[(1, 0)], 0.01 * NUM_AOS as f64);
+        assert_eq!(params.mo_info.iter().map(|i| i.occ).collect::<Vec<_>>(),
+                   [Some(2.0), Some(0.0)]);
+    }
+
+    #[test]
+    fn unrestricted_mos_follow_alpha_with_beta() {
+        let params = parse(&fchk(true)).unwrap();
+
+        assert_eq!(params.mo_coefs.nrows(), 4);
+        assert_eq!(params.mo_info.iter().map(|i| (i.spin, i.occ)).collect::<Vec<_>>(), [
+            (Spin::Alpha, Some(1.0)), (Spin::Alpha, Some(0.0)),
+            (Spin::Beta, Some(1.0)), (Spin::Beta, Some(0.0)),
+        ]);
+        assert_eq!(params.mo_info[2].energy, Some(-20.2));
+    }
+
+    #[test]
+    fn rejects_a_basis_function_count_off_the_shells() {
+        let count = |n: usize| {
+            let mut line = String::new();
+
+            scalar(&mut line, "Number of basis functions", n as i64);
+            line
+        };
+        let text = fchk(false).replace(&count(NUM_AOS), &count(NUM_AOS + 1));
+
+        assert_ne!(text, fchk(false));
+
+        assert!(parse(&text).is_err());
+    }
+}
@@ -0,0 +1,252 @@
+use std::f64::consts::PI;
+
+pub const MAX_L: i32 = 4;
+
+pub const L_LABELS: [char; MAX_L as usize + 1] = ['s', 'p', 'd', 'f', 'g'];
+
+// how many solid harmonics there are up to MAX_L, which is also where the
+// cartesian components start in the shader's angular functions
+pub const NUM_LM: usize = ((MAX_L + 1) * (MAX_L + 1)) as usize;
+
+pub fn lm_index(l: i32, m: i32) -> usize {
+    (l * l + l + m) as usize
+}
+
+pub fn num_cart(l: i32) -> usize {
+    ((l + 1) * (l + 2) / 2) as usize
+}
+
+// the k-th component of cart_pows(l), counting every lower l's first
+pub fn cart_index(l: i32, k: usize) -> usize {
+    (l * (l + 1) * (l + 2) / 6) as usize + k
+}
+
+// a cartesian shell's components, with the x power falling first, so d
+// runs xx xy xz yy yz zz
+pub fn cart_pows(l: i32) -> Vec<[u32; 3]> {
+    let l = l as u32;
+
+    (0..=l).rev()
+           .flat_map(|i| (0..=(l - i)).rev().map(move |j| [i, j, l - i - j]))
+           .collect()
+}
+
+// one monomial of a solid harmonic
+#[derive(Debug, Clone, Copy)]
+pub struct Term {
+    pub coef:   f64,
+    pub pows:   [u32; 3],
+}
+
+fn factorial(n: i32) -> f64 {
+    (1..=n).map(|k| k as f64)
+           .product()
+}
+
+pub fn double_factorial(n: i32) -> f64 {
+    (1..=n).rev()
+           .step_by(2)
+           .map(|k| k as f64)
+           .product()
+}
+
+fn binomial(n: i32, k: i32) -> f64 {
+    if k < 0 || k > n {
+        0.0
+    } else {
+        factorial(n) / (factorial(k) * factorial(n - k))
+    }
+}
+
+// r^l times the real spherical harmonic, written out in x, y and z following
+// helgaker, jorgensen and olsen (6.4.48), then rescaled from racah's
+// normalization to unit norm on the sphere
+pub fn solid_harmonic(l: i32, m: i32) -> Vec<Term> {
+    let abs_m = m.abs();
+    // v runs over half integers for negative m, so this counts in 2v
+    let v2_min = if m < 0 { 1 } else { 0 };
+    let racah = (2.0 * factorial(l + abs_m) * factorial(l - abs_m)
+                 / if m == 0 { 2.0 } else { 1.0 }).sqrt()
+              / (2f64.powi(abs_m) * factorial(l));
+    let norm = racah * ((2 * l + 1) as f64 / (4.0 * PI)).sqrt();
+    let mut terms: Vec<Term> = Vec::new();
+
+    for t in 0..=((l - abs_m) / 2) {
+        for u in 0..=t {
+            for v2 in (v2_min..=abs_m).step_by(2) {
+                let sign = if (t + (v2 - v2_min) / 2) % 2 == 0 { 1.0 } else { -1.0 };
+                let coef = sign * 0.25f64.powi(t)
+                         * binomial(l, t) * binomial(l - t, abs_m + t)
+                         * binomial(t, u) * binomial(abs_m, v2);
+                let pows = [
+                    (2 * t + abs_m - 2 * u - v2) as u32,
+                    (2 * u + v2) as u32,
+                    (l - 2 * t - abs_m) as u32,
+                ];
+
+                match terms.iter_mut().find(|term| term.pows == pows) {
+                    Some(term) => term.coef += norm * coef,
+                    None => terms.push(Term { coef: norm * coef, pows }),
+                }
+            }
+        }
+    }
+
+    terms.retain(|term| term.coef.abs() > 1e-12);
+    terms
+}
+
+// every solid harmonic up to MAX_L, indexed by lm_index
+pub fn table() -> Vec<Vec<Term>> {
+    (0..=MAX_L).flat_map(|l| (-l..=l).map(move |m| solid_harmonic(l, m)))
+               .collect()
+}
+
+// x^i y^j z^k over the angular part of its norm; the rest depends on the
+// exponent, so it goes with the radial part instead
+pub fn cartesian(pows: [u32; 3]) -> Term {
+    let norm = pows.iter()
+                   .map(|&n| double_factorial(2 * n as i32 - 1))
+                   .product::<f64>();
+
+    Term { coef: 1.0 / norm.sqrt(), pows }
+}
+
+// every cartesian component up to MAX_L, indexed by cart_index
+pub fn cart_table() -> Vec<Term> {
+    (0..=MAX_L).flat_map(cart_pows)
+               .map(cartesian)
+               .collect()
+}
+
+pub fn eval(terms: &[Term], pos: [f64; 3]) -> f64 {
+    terms.iter()
+         .map(|term| {
+             term.coef * pos[0].powi(term.pows[0] as i32)
+                       * pos[1].powi(term.pows[1] as i32)
+                       * pos[2].powi(term.pows[2] as i32)
+         })
+         .sum()
+}
+
+pub fn gradient(terms: &[Term], pos: [f64; 3]) -> [f64; 3] {
+    let mut grad = [0.0; 3];
+
+    for term in terms {
+        for (i, g) in grad.iter_mut().enumerate() {
+            if term.pows[i] == 0 {
+                continue;
+            }
+
+            *g += term.coef * term.pows[i] as f64
+                * (0..3).map(|j| pos[j].powi(term.pows[j] as i32 - (i == j) as i32))
+                        .product::<f64>();
+        }
+    }
+
+    grad
+}
+
+// the polynomial for one component of the gradient, for the shader to
+// have analytic normals
+pub fn derivative(terms: &[Term], axis: usize) -> Vec<Term> {
+    terms.iter()
+         .filter(|term| term.pows[axis] > 0)
+         .map(|term| {
+             let mut pows = term.pows;
+             pows[axis] -= 1;
+
+             Term { coef: term.coef * term.pows[axis] as f64, pows }
+         })
+         .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // nodes and weights on [-1, 1], exact for polynomials below degree 2n
+    fn gauss_legendre(n: usize) -> Vec<(f64, f64)> {
+        (0..n).map(|i| {
+                  let mut x = (PI * (i as f64 + 0.75) / (n as f64 + 0.5)).cos();
+
+                  loop {
+                      // p_n(x) and p_n-1(x) by the three term recurrence
+                      let (mut p0, mut p1) = (1.0, x);
+
+                      for k in 2..=n {
+                          let k = k as f64;
+
+                          (p0, p1) = (p1, ((2.0 * k - 1.0) * x * p1 - (k - 1.0) * p0) / k);
+                      }
+
+                      let dp = n as f64 * (x * p1 - p0) / (x * x - 1.0);
+                      let dx = p1 / dp;
+
+                      x -= dx;
+
+                      if dx.abs() < 1e-15 {
+                          return (x, 2.0 / ((1.0 - x * x) * dp * dp));
+                      }
+                  }
+              })
+              .collect()
+    }
+
+    // gauss-legendre in cos(theta) and evenly spaced in phi, which together
+    // integrate the products of any two harmonics up to MAX_L exactly
+    fn sphere_grid() -> Vec<([f64; 3], f64)> {
+        let num_phi = 4 * MAX_L as usize + 2;
+        let phi_weight = 2.0 * PI / num_phi as f64;
+        let mut grid = Vec::new();
+
+        for (z, weight) in gauss_legendre(MAX_L as usize + 1) {
+            let rho = (1.0 - z * z).sqrt();
+
+            for k in 0..num_phi {
+                let phi = 2.0 * PI * k as f64 / num_phi as f64;
+
+                grid.push(([rho * phi.cos(), rho * phi.sin(), z], weight * phi_weight));
+            }
+        }
+
+        grid
+    }
+
+    #[test]
+    fn solid_harmonics_are_orthonormal_on_the_sphere() {
+        let grid = sphere_grid();
+        let table = table();
+
+        assert_eq!(table.len(), NUM_LM);
+
+        for l in 0..=MAX_L {
+            for m in -l..=l {
+                for l2 in 0..=MAX_L {
+                    for m2 in -l2..=l2 {
+                        let (a, b) = (&table[lm_index(l, m)], &table[lm_index(l2, m2)]);
+                        let overlap = grid.iter()
+                                          .map(|&(pos, w)| w * eval(a, pos) * eval(b, pos))
+                                          .sum::<f64>();
+                        let expected = if (l, m) == (l2, m2) { 1.0 } else { 0.0 };
+
+                        assert!((overlap - expected).abs() < 1e-12,
+                                "<{l} {m}|{l2} {m2}> = {overlap}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn solid_harmonics_are_homogeneous() {
+        for l in 0..=MAX_L {
+            for m in -l..=l {
+                let terms = solid_harmonic(l, m);
+
+                assert!(!terms.is_empty());
+                assert!(terms.iter().all(|t| t.pows.iter().sum::<u32>() == l as u32));
+            }
+        }
+    }
+}
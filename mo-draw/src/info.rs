@@ -0,0 +1,70 @@
+use std::fmt::Write;
+use crate::{
+    element,
+    error::Error,
+    mo_coefs::Spin,
+    Orbitals, Params,
+};
+
+fn optional(x: Option<f64>) -> String {
+    x.map_or("-".into(), |x| format!("{x:.6}"))
+}
+
+pub fn format(params: &Params) -> Result<String, Error> {
+    let mut s = String::new();
+
+    writeln!(s, "atoms: {}", params.atoms.len())?;
+
+    for (i, atom) in params.atoms.iter().enumerate() {
+        let [x, y, z] = atom.position;
+
+        writeln!(s, "  {:4} {:2} {x:12.6} {y:12.6} {z:12.6}  basis {}",
+                 i, element::symbol(atom.atomic_num), atom.basis_id)?;
+    }
+
+    match &params.orbitals {
+        Orbitals::Contracted(bases) => {
+            writeln!(s, "bases: {}", bases.len())?;
+
+            for (i, basis) in bases.iter().enumerate() {
+                // each shell with its own contraction length
+                let shells = basis.shell_labels()
+                                  .into_iter()
+                                  .zip(&basis.shells)
+                                  .map(|(label, shell)| format!("{label}({})", shell.expns.len()))
+                                  .collect::<Vec<_>>()
+                                  .join(" ");
+
+                writeln!(s, "  {:4} {shells}", i)?;
+            }
+        },
+        Orbitals::Primitive(prims) => {
+            let max_l = prims.iter()
+                             .map(|p| p.pows.iter().sum::<u32>())
+                             .max()
+                             .unwrap_or(0);
+
+            writeln!(s, "primitives: {}, up to l = {max_l}", prims.len())?;
+        },
+        Orbitals::Volume(volume) => {
+            let [nx, ny, nz] = volume.grid.shape;
+
+            writeln!(s, "volume: {nx} x {ny} x {nz} points")?;
+        },
+    }
+
+    writeln!(s, "aos: {}", params.num_aos())?;
+    writeln!(s, "mos: {}", params.mo_coefs.nrows())?;
+
+    for (i, info) in params.mo_info.iter().enumerate() {
+        let spin = match info.spin {
+            Spin::Alpha => "alpha",
+            Spin::Beta => "beta",
+        };
+
+        writeln!(s, "  {:4} {spin:5} energy {:>12} occ {:>9}",
+                 i, optional(info.energy), optional(info.occ))?;
+    }
+
+    Ok(s)
+}
@@ -0,0 +1,148 @@
+use std::{
+    fs,
+    io::{ self, Read },
+    path::{ Path, PathBuf },
+    str::FromStr,
+};
+use clap::ValueEnum;
+use crate::{
+    basis::AoOrdering,
+    cube,
+    error::Error,
+    fchk, molden, molfile, scene, wfn,
+    Params,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Molden,
+    Fchk,
+    Wfn,
+    Wfx,
+    Json,
+    Toml,
+    Cube,
+    Molfile,
+    Sdf,
+}
+
+impl Format {
+    // lets a file extension pick between the formats that share a flag
+    pub fn refine(self, source: &Source) -> Self {
+        match (self, source.extension()) {
+            (Self::Wfn, Some("wfx")) => Self::Wfx,
+            (Self::Json, Some("toml")) => Self::Toml,
+            (Self::Molfile, Some("sdf")) => Self::Sdf,
+            _ => self,
+        }
+    }
+
+    // what each format's writers use, unless told otherwise; scenes say
+    // for themselves
+    pub fn ordering(self) -> AoOrdering {
+        match self {
+            Self::Molden => AoOrdering::Molden,
+            Self::Fchk => AoOrdering::Gaussian,
+            _ => AoOrdering::Native,
+        }
+    }
+
+    pub fn parse(self, text: &str) -> Result<Params, Error> {
+        match self {
+            Self::Molden => molden::parse(text),
+            Self::Fchk => fchk::parse(text),
+            Self::Wfn => wfn::parse_wfn(text),
+            Self::Wfx => wfn::parse_wfx(text),
+            Self::Json => scene::parse_json(text),
+            Self::Toml => scene::parse_toml(text),
+            Self::Cube => cube::parse(text),
+            Self::Molfile => Ok(molfile::parse(text)?.to_params()),
+            // only the first record, since there's one geometry to draw
+            Self::Sdf => {
+                let records = molfile::parse_sdf(text)?;
+                let first = records.first()
+                                   .ok_or("sdf file has no records")?;
+
+                Ok(first.to_params())
+            },
+        }
+    }
+}
+
+// named pipes are just paths, so they come in as files
+#[derive(Debug, Clone)]
+pub enum Source {
+    Stdin,
+    File(PathBuf),
+    Socket(PathBuf),
+}
+
+impl FromStr for Source {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "-" => Self::Stdin,
+            _ => match s.strip_prefix("unix:") {
+                Some(path) => Self::Socket(PathBuf::from(path)),
+                None => Self::File(PathBuf::from(s)),
+            },
+        })
+    }
+}
+
+impl Source {
+    fn extension(&self) -> Option<&str> {
+        match self {
+            Self::File(path) => path.extension()
+                                    .and_then(|s| s.to_str()),
+            _ => None,
+        }
+    }
+
+    pub fn read_to_string(&self) -> Result<String, Error> {
+        match self {
+            Self::Stdin => {
+                let mut text = String::new();
+                io::stdin().read_to_string(&mut text)?;
+
+                Ok(text)
+            },
+            Self::File(path) => Ok(fs::read_to_string(path)?),
+            Self::Socket(path) => read_socket(path),
+        }
+    }
+}
+
+// listens on the socket and takes the whole document from the first client
+#[cfg(unix)]
+fn read_socket(path: &Path) -> Result<String, Error> {
+    use std::os::unix::net::UnixListener;
+
+    let listener = UnixListener::bind(path)?;
+    let accepted = listener.accept();
+
+    drop(listener);
+    fs::remove_file(path)?;
+
+    let mut text = String::new();
+    accepted?.0
+             .read_to_string(&mut text)?;
+
+    Ok(text)
+}
+
+#[cfg(not(unix))]
+fn read_socket(_path: &Path) -> Result<String, Error> {
+    Err("unix sockets are not supported on this platform".into())
+}
+
+pub fn read(source:     &Source,
+            format:     Format,
+            ordering:   Option<AoOrdering>) -> Result<Params, Error> {
+    let mut params = format.parse(&source.read_to_string()?)?;
+
+    params.reorder_aos(ordering.unwrap_or(format.ordering()))?;
+
+    Ok(params)
+}
@@ -1,5 +1,9 @@
-use std::num::NonZeroU32;
-use clap::{ Parser };
+use std::{
+    num::NonZeroU32,
+    panic,
+    process,
+};
+use clap::Parser;
 use ndarray::{ Array1, Array2 };
 use raw_window_handle::{
     HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle,
@@ -15,7 +19,10 @@ use winit::platform::unix;
 
 use glutin::{
     config::{ Config, ConfigSurfaceTypes, ConfigTemplateBuilder },
-    context::{ ContextApi, ContextAttributesBuilder, NotCurrentContext },
+    context::{
+        ContextApi, ContextAttributesBuilder, NotCurrentContext,
+        PossiblyCurrentContext,
+    },
     display::{ Display, DisplayApiPreference },
     prelude::*,
     surface::{ Surface, SurfaceAttributesBuilder, WindowSurface },
@@ -23,31 +30,38 @@ use glutin::{
 
 pub mod atom;
 pub mod basis;
+pub mod camera;
+pub mod cli;
 pub mod controller;
+pub mod cube;
 pub mod drawer;
+pub mod element;
 pub mod error;
+pub mod eval;
 pub mod event;
+pub mod fchk;
 pub mod gl;
+pub mod harmonics;
+pub mod info;
+pub mod input;
+pub mod mesh;
 pub mod mo_coefs;
+pub mod molden;
+pub mod molfile;
+pub mod render;
+pub mod scene;
+pub mod validate;
+pub mod wfn;
 
 use atom::Atom;
-use basis::Basis;
-use drawer::Drawer;
+use basis::{ AoOrdering, Basis, Primitive };
+use cli::{ Args, Input };
+use drawer::{ Drawer, Renderer, Settings };
 use controller::Controller;
+use cube::Volume;
 use event::{ Frame, HandleEvent };
-
-#[derive(Parser, Debug)]
-#[command(name = "CH121 Final MO Drawer")]
-#[command(author = "James Moore <jam0152@uah.edu>")]
-#[command(about = "Visualizes molecular electron orbitals", long_about = None)]
-struct Args {
-    #[arg(short = 'B', long = "basis")]
-    bases: Vec<String>,
-    #[arg(short = 'A', long = "atom")]
-    atoms: Vec<String>,
-    #[arg(short = 'C', long = "coefs")]
-    coefs: String,
-}
+use error::Error;
+use mo_coefs::MoInfo;
 
 fn collect_argvs(raw_argv: &Vec<String>) -> Vec<Vec<String>> {
     let mut argvs = Vec::new();
@@ -69,33 +83,100 @@ fn collect_argvs(raw_argv: &Vec<String>) -> Vec<Vec<String>> {
     argvs
 }
 
+#[derive(Debug)]
+pub enum Orbitals {
+    Contracted(Array1<Basis>),
+    Primitive(Array1<Primitive>),
+    Volume(Volume),
+}
+
 #[derive(Debug)]
 pub struct Params {
-    pub bases:      Array1<Basis>,
     pub atoms:      Array1<Atom>,
+    pub orbitals:   Orbitals,
     pub mo_coefs:   Array2<f64>,
+    pub mo_info:    Array1<MoInfo>,
+    pub settings:   Settings,
 }
 
 impl Params {
-    fn from_args(args: Args) -> Self {
-        let bases = args.bases.into_iter()
-                              .map(|s| Basis::from_arg(&s).unwrap())
-                              .collect();
+    fn from_input(input: Input) -> Result<Self, Error> {
+        let params = Self::geometry_from_input(input)?;
 
-        let atoms = args.atoms.into_iter()
-                              .map(|s| Atom::from_arg(&s).unwrap())
-                              .collect();
+        if params.mo_coefs.nrows() == 0 {
+            return Err("the input has no mos to draw, molfiles and sdf files need --basis and --coefs alongside".into());
+        }
 
-        let mo_coefs = mo_coefs::from_arg(&args.coefs)
-                                .unwrap();
+        Ok(params)
+    }
 
-        Self { bases, atoms, mo_coefs }
+    // like from_input, but molfiles and sdf files may stand alone with just
+    // their atoms, for what only looks at the geometry
+    fn geometry_from_input(input: Input) -> Result<Self, Error> {
+        // a document stands alone, unless it's only there for its geometry
+        let geometry = match input.source() {
+            Some((source, format)) if input.coefs.is_none() => {
+                return input::read(source, format, input.ordering);
+            },
+            Some((source, format)) => Some(input::read(source, format, None)?.atoms),
+            None => None,
+        };
+
+        let bases = input.bases.into_iter()
+                               .map(|s| Basis::from_arg(&s))
+                               .collect::<Result<_, _>>()?;
+
+        let atoms = match geometry {
+            Some(atoms) => atoms,
+            None => input.atoms.into_iter()
+                               .map(|s| Atom::from_arg(&s))
+                               .collect::<Result<_, _>>()?,
+        };
+
+        let mo_coefs = mo_coefs::from_arg(&input.coefs.unwrap())?;
+        let mo_info = (0..mo_coefs.nrows()).map(|_| MoInfo::default())
+                                           .collect();
+
+        Ok(Self {
+            atoms,
+            orbitals:   Orbitals::Contracted(bases),
+            mo_coefs,
+            mo_info,
+            settings:   Settings::default(),
+        })
+    }
+
+    // what every mo is expected to have a coefficient for
+    pub fn num_aos(&self) -> usize {
+        match &self.orbitals {
+            Orbitals::Contracted(bases) => {
+                self.atoms.iter()
+                          .map(|a| bases.get(a.basis_id).map_or(0, |b| b.num_aos()))
+                          .sum()
+            },
+            Orbitals::Primitive(prims) => prims.len(),
+            Orbitals::Volume(_) => 0,
+        }
+    }
+
+    // brings the mo coefficients over from the ao order a program wrote
+    pub fn reorder_aos(&mut self, ordering: AoOrdering) -> Result<(), Error> {
+        // geometry alone has no mos, nor any bases to check them against
+        if self.mo_coefs.is_empty() {
+            return Ok(());
+        }
+
+        if let Orbitals::Contracted(bases) = &self.orbitals {
+            self.mo_coefs = ordering.to_native(&self.atoms, bases, &self.mo_coefs)?;
+        }
+
+        Ok(())
     }
 }
 
 #[allow(unused_variables)]
 fn create_gl_display(raw_display: RawDisplayHandle,
-                     raw_window: RawWindowHandle) -> Display {
+                     raw_window: RawWindowHandle) -> Result<Display, Error> {
     #[cfg(egl_backend)]
     let preference = DisplayApiPreference::Egl;
 
@@ -114,12 +195,11 @@ fn create_gl_display(raw_display: RawDisplayHandle,
     #[cfg(all(egl_backend, glx_backend))]
     let preference = DisplayApiPreference::GlxThenEgl(Box::new(unix::register_xlib_error_hook));
 
-    unsafe { Display::new(raw_display, preference)
-                     .unwrap() }
+    unsafe { Ok(Display::new(raw_display, preference)?) }
 }
 
 fn find_gl_config(gl_display: &Display,
-                  raw_window: RawWindowHandle) -> Config {
+                  raw_window: RawWindowHandle) -> Result<Config, Error> {
     let template = {
         let builder = ConfigTemplateBuilder::new()
                                             .with_alpha_size(8)
@@ -132,8 +212,7 @@ fn find_gl_config(gl_display: &Display,
     };
 
     unsafe {
-        gl_display.find_configs(template)
-                  .unwrap()
+        gl_display.find_configs(template)?
                   .reduce(|acc, c| {
                       if c.num_samples() > acc.num_samples() {
                           c
@@ -141,13 +220,13 @@ fn find_gl_config(gl_display: &Display,
                           acc
                       }
                   })
-                  .unwrap()
+                  .ok_or_else(|| "no usable gl config".into())
     }
 }
 
 fn create_gl_context(gl_display: &Display,
                      raw_window: RawWindowHandle,
-                     gl_config: &Config) -> NotCurrentContext {
+                     gl_config: &Config) -> Result<NotCurrentContext, Error> {
     let ctx_attrs =
         ContextAttributesBuilder::new()
                                  .build(Some(raw_window));
@@ -157,81 +236,129 @@ fn create_gl_context(gl_display: &Display,
                                  .build(Some(raw_window));
     unsafe {
         gl_display.create_context(&gl_config, &ctx_attrs)
-                  .unwrap_or_else(|_| {
+                  .or_else(|_| {
         gl_display.create_context(&gl_config, &fallback_ctx_attrs)
-                  .expect("failed to create gl context")
-        })
+                  })
+                  .map_err(Into::into)
     }
 }
 
 fn create_gl_surface(gl_display:    &Display,
                      window:        &Window,
-                     gl_config:     &Config) -> Surface<WindowSurface> {
+                     gl_config:     &Config) -> Result<Surface<WindowSurface>, Error> {
     let size = window.inner_size();
     let width = NonZeroU32::new(size.width)
-                           .unwrap();
+                           .ok_or("the window has no width")?;
     let height = NonZeroU32::new(size.height)
-                            .unwrap();
+                            .ok_or("the window has no height")?;
     let raw_window = window.raw_window_handle();
     let attrs =
         SurfaceAttributesBuilder::<WindowSurface>
                                 ::new()
                                  .build(raw_window, width, height);
 
-    unsafe { gl_display.create_window_surface(gl_config, &attrs)
-                       .unwrap() }
+    unsafe { Ok(gl_display.create_window_surface(gl_config, &attrs)?) }
 }
 
-fn main() {
-    let args = Args::parse();
-    let params = Params::from_args(args);
-
-    let event_loop = EventLoop::new();
-    let raw_display = event_loop.raw_display_handle();
-    let window = WindowBuilder::new()
-                               .with_title("mo-draw")
-                               .build(&event_loop)
-                               .unwrap();
-    let raw_window = window.raw_window_handle();
-    let gl_display = create_gl_display(raw_display, raw_window);
-    let gl_config = find_gl_config(&gl_display, raw_window);
-    let gl_surface = create_gl_surface(&gl_display, &window, &gl_config);
-    let gl_context = create_gl_context(&gl_display, raw_window, &gl_config);
-    let gl_context = gl_context.make_current(&gl_surface)
-                               .unwrap();
+// winit panics rather than erroring when there's no display server to talk
+// to, as over ssh without x forwarding, so the panic is caught and kept quiet
+fn create_event_loop() -> Result<EventLoop<()>, Error> {
+    let hook = panic::take_hook();
+
+    panic::set_hook(Box::new(|_| ()));
+
+    let event_loop = panic::catch_unwind(EventLoop::new);
+
+    panic::set_hook(hook);
+
+    event_loop.map_err(|_| "no display server to open a window on".into())
+}
+
+struct GlWindow {
+    event_loop: EventLoop<()>,
+    window:     Window,
+    display:    Display,
+    surface:    Surface<WindowSurface>,
+    context:    PossiblyCurrentContext,
+}
+
+impl GlWindow {
+    fn new() -> Result<Self, Error> {
+        let event_loop = create_event_loop()?;
+        let raw_display = event_loop.raw_display_handle();
+        let window = WindowBuilder::new()
+                                   .with_title("mo-draw")
+                                   .build(&event_loop)?;
+        let raw_window = window.raw_window_handle();
+        let display = create_gl_display(raw_display, raw_window)?;
+        let config = find_gl_config(&display, raw_window)?;
+        let surface = create_gl_surface(&display, &window, &config)?;
+        let context = create_gl_context(&display, raw_window, &config)?
+                                       .make_current(&surface)?;
+
+        Ok(Self { event_loop, window, display, surface, context })
+    }
+}
+
+fn view(params: Params) -> Result<(), Error> {
+    // drawing offscreen on the cpu needs neither a display nor a gpu
+    let no_window = |err: Error| -> Error {
+        format!("{err}; export image --cpu draws to .png files without a window").into()
+    };
+
+    let GlWindow {
+        event_loop,
+        window,
+        display: gl_display,
+        surface: gl_surface,
+        context: gl_context,
+    } = GlWindow::new().map_err(no_window)?;
 
     let mut frame = Frame::initial();
-    let mut controller = Controller::new();
-    let drawer = Drawer::new(&gl_display, &params);
+    let mut controller = Controller::new(&params);
+    let mut drawer = Drawer::new(&gl_display, &params).map_err(no_window)?;
+    let mut title = String::new();
 
     event_loop.run(move |evt, _, ctl_flow| {
         *ctl_flow = ControlFlow::Wait;
 
-        frame.next();
+        if let Event::RedrawEventsCleared = evt {
+            frame.next();
+        }
+
         controller.handle_event(&frame, &evt);
 
+        if controller.vars.quit {
+            *ctl_flow = ControlFlow::Exit;
+            return;
+        }
+
         match evt {
-            Event::WindowEvent { event, .. } => match event {
-                WindowEvent::Resized(size) => {
-                    if size.width != 0 && size.height != 0 {
-                        let width = NonZeroU32::new(size.width)
-                                               .unwrap();
-                        let height = NonZeroU32::new(size.height)
-                                                .unwrap();
-
-                        gl_surface.resize(&gl_context, width, height);
-                        drawer.resize(size.width, size.height);
-                    }
-                },
-                WindowEvent::CloseRequested => {
-                    *ctl_flow = ControlFlow::Exit;
-                },
-                _ => (),
+            Event::WindowEvent { event: WindowEvent::Resized(size), .. }
+                if size.width != 0 && size.height != 0 => {
+                let width = NonZeroU32::new(size.width)
+                                       .unwrap();
+                let height = NonZeroU32::new(size.height)
+                                        .unwrap();
+
+                gl_surface.resize(&gl_context, width, height);
+                drawer.resize(size.width, size.height);
             },
             Event::RedrawEventsCleared => {
-                drawer.draw_mo(controller.vars.mo_idx);
+                let vars = &controller.vars;
+
+                drawer.set_mode(vars.mode);
+                drawer.set_iso(vars.iso);
+                drawer.set_density(vars.density);
+                drawer.set_camera(&vars.camera);
+                drawer.draw_mo(vars.mo_idx);
                 window.request_redraw();
 
+                if controller.title() != title {
+                    title = controller.title();
+                    window.set_title(&title);
+                }
+
                 gl_surface.swap_buffers(&gl_context)
                           .unwrap();
             },
@@ -239,3 +366,12 @@ fn main() {
         }
     });
 }
+
+fn main() {
+    let args = Args::parse();
+
+    if let Err(err) = args.command.run() {
+        eprintln!("error: {err}");
+        process::exit(1);
+    }
+}
@@ -0,0 +1,524 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    path::Path,
+};
+use clap::ValueEnum;
+use ndarray::{ s, Array2, Array3 };
+use serde_json::json;
+use crate::{
+    cube::{ self, Grid },
+    error::Error,
+    eval::Evaluator,
+    Params,
+};
+
+// the same reds and blues for each phase in every format that has colours
+pub const POSITIVE_COLOR: [f64; 3] = [0.85, 0.25, 0.2];
+pub const NEGATIVE_COLOR: [f64; 3] = [0.2, 0.35, 0.85];
+
+// cube corners are numbered with x in the lowest bit, then y, then z
+const EDGES: [[usize; 2]; 12] = [
+    [0, 1], [2, 3], [4, 5], [6, 7],
+    [0, 2], [1, 3], [4, 6], [5, 7],
+    [0, 4], [1, 5], [2, 6], [3, 7],
+];
+// each face's corners in order around it
+const FACES: [[usize; 4]; 6] = [
+    [0, 2, 6, 4], [1, 3, 7, 5],
+    [0, 1, 5, 4], [2, 3, 7, 6],
+    [0, 1, 3, 2], [4, 5, 7, 6],
+];
+
+fn edge_between(a: usize, b: usize) -> usize {
+    EDGES.iter()
+         .position(|&[u, v]| (u, v) == (a, b) || (u, v) == (b, a))
+         .unwrap()
+}
+
+// the loops of crossed edges the surface cuts through a cube with the given
+// corners inside it; faces with two inside corners across from each other
+// cut each one off on its own, which both cubes sharing the face agree on,
+// so the surface comes out closed
+fn cube_loops(inside: u8) -> Vec<Vec<usize>> {
+    let is_inside = |corner: usize| inside >> corner & 1 == 1;
+    let mut links: [Vec<usize>; 12] = Default::default();
+    let mut link = |a: usize, b: usize| {
+        links[a].push(b);
+        links[b].push(a);
+    };
+
+    for face in FACES {
+        let crossed = (0..4).filter(|&k| is_inside(face[k]) != is_inside(face[(k + 1) % 4]))
+                            .map(|k| edge_between(face[k], face[(k + 1) % 4]))
+                            .collect::<Vec<_>>();
+
+        match crossed.len() {
+            2 => link(crossed[0], crossed[1]),
+            4 => {
+                for k in (0..4).filter(|&k| is_inside(face[k])) {
+                    link(edge_between(face[(k + 3) % 4], face[k]),
+                         edge_between(face[k], face[(k + 1) % 4]));
+                }
+            },
+            _ => (),
+        }
+    }
+
+    let mut loops = Vec::new();
+    let mut visited = [false; 12];
+
+    for start in 0..12 {
+        if visited[start] || links[start].is_empty() {
+            continue;
+        }
+
+        let mut edges = vec![start];
+        let (mut prev, mut cur) = (start, links[start][0]);
+        visited[start] = true;
+
+        while cur != start {
+            visited[cur] = true;
+            edges.push(cur);
+
+            let next = if links[cur][0] == prev { links[cur][1] } else { links[cur][0] };
+            (prev, cur) = (cur, next);
+        }
+
+        loops.push(edges);
+    }
+
+    loops
+}
+
+// every cube configuration's triangles, as fans over its loops
+fn triangle_table() -> Vec<Vec<[usize; 3]>> {
+    (0..=255u8).map(|inside| {
+                   cube_loops(inside).into_iter()
+                                     .flat_map(|l| (1..l.len() - 1).map(move |i| [l[0], l[i], l[i + 1]]))
+                                     .collect()
+               })
+               .collect()
+}
+
+// an indexed triangle mesh, in bohr, wound counterclockwise seen from
+// outside the lobe
+#[derive(Debug, Default)]
+pub struct Mesh {
+    pub positions:  Vec<[f64; 3]>,
+    pub normals:    Vec<[f64; 3]>,
+    pub triangles:  Vec<[u32; 3]>,
+}
+
+// where values rise above iso; vertices are shared by every cube around the
+// grid edge they sit on
+fn march(grid: &Grid, values: &Array3<f64>, iso: f64) -> Mesh {
+    let table = triangle_table();
+    let [nx, ny, nz] = grid.shape;
+    let mut vertices: HashMap<([usize; 3], usize), u32> = HashMap::new();
+    let mut mesh = Mesh::default();
+
+    for i in 0..nx.saturating_sub(1) {
+        for j in 0..ny.saturating_sub(1) {
+            for k in 0..nz.saturating_sub(1) {
+                let corner = |c: usize| [i + (c & 1), j + (c >> 1 & 1), k + (c >> 2 & 1)];
+                let inside = (0..8).filter(|&c| values[corner(c)] > iso)
+                                   .fold(0u8, |mask, c| mask | 1 << c);
+
+                for tri in &table[inside as usize] {
+                    let tri = tri.map(|e| {
+                        let [a, b] = EDGES[e].map(corner);
+                        let axis = (0..3).find(|&n| a[n] != b[n])
+                                         .unwrap();
+
+                        *vertices.entry((a, axis))
+                                 .or_insert_with(|| {
+                                     let (va, vb) = (values[a], values[b]);
+                                     let t = (iso - va) / (vb - va);
+                                     let (pa, pb) = (grid.point(a), grid.point(b));
+
+                                     mesh.positions.push([0, 1, 2].map(|n| pa[n] + t * (pb[n] - pa[n])));
+                                     mesh.positions.len() as u32 - 1
+                                 })
+                    });
+
+                    mesh.triangles.push(tri);
+                }
+            }
+        }
+    }
+
+    mesh
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+// normals point down the gradient of sign * psi, away from the lobe, and
+// every triangle is turned to face the same way
+fn orient(mesh:         &mut Mesh,
+          evaluator:    &Evaluator,
+          params:       &Params,
+          mo_idx:       usize,
+          sign:         f64) -> Result<(), Error> {
+    let points = Array2::from_shape_fn((mesh.positions.len(), 3), |(n, i)| mesh.positions[n][i]);
+    let mo_coefs = params.mo_coefs.slice(s![mo_idx..=mo_idx, ..]);
+    let grads = evaluator.mo_gradients(mo_coefs, points.view())?;
+
+    mesh.normals = grads.outer_iter()
+                        .map(|g| {
+                            let n = [0, 1, 2].map(|i| -sign * g[(0, i)]);
+                            let len = dot(n, n).sqrt();
+
+                            if len > 0.0 { n.map(|x| x / len) } else { n }
+                        })
+                        .collect();
+
+    let (positions, normals) = (&mesh.positions, &mesh.normals);
+
+    mesh.triangles.retain_mut(|tri| {
+        let [a, b, c] = tri.map(|v| positions[v as usize]);
+        let face = cross(sub(b, a), sub(c, a));
+        let normal = tri.iter()
+                        .map(|&v| normals[v as usize])
+                        .fold([0.0; 3], |acc, n| [acc[0] + n[0], acc[1] + n[1], acc[2] + n[2]]);
+
+        if dot(face, normal) < 0.0 {
+            tri.swap(1, 2);
+        }
+
+        // corners right on the isovalue leave slivers with no area
+        dot(face, face) > 0.0
+    });
+
+    Ok(())
+}
+
+// the lobes where an mo rises above iso and where it falls below -iso
+#[derive(Debug)]
+pub struct Isosurface {
+    pub mo_idx:     usize,
+    pub iso:        f64,
+    pub positive:   Mesh,
+    pub negative:   Mesh,
+}
+
+impl Isosurface {
+    pub fn extract(params: &Params, mo_idx: usize, iso: f64, grid: &Grid) -> Result<Self, Error> {
+        if !iso.is_finite() || iso <= 0.0 {
+            return Err("the isovalue must be positive".into());
+        }
+
+        let evaluator = Evaluator::new(params)?;
+        let values = cube::evaluate(params, mo_idx, grid)?;
+
+        let mut positive = march(grid, &values, iso);
+        let mut negative = march(grid, &values.mapv(|v| -v), iso);
+
+        orient(&mut positive, &evaluator, params, mo_idx, 1.0)?;
+        orient(&mut negative, &evaluator, params, mo_idx, -1.0)?;
+
+        Ok(Self { mo_idx, iso, positive, negative })
+    }
+
+    fn phases(&self) -> [(&str, &Mesh, [f64; 3]); 2] {
+        [
+            ("positive", &self.positive, POSITIVE_COLOR),
+            ("negative", &self.negative, NEGATIVE_COLOR),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MeshFormat {
+    Obj,
+    Ply,
+    Stl,
+    Gltf,
+}
+
+impl MeshFormat {
+    // wavefront obj unless the extension says otherwise
+    pub fn from_path(path: Option<&Path>) -> Self {
+        match path.and_then(|p| p.extension())
+                  .and_then(|e| e.to_str())
+                  .map(|e| e.to_ascii_lowercase())
+                  .as_deref() {
+            Some("ply") => Self::Ply,
+            Some("stl") => Self::Stl,
+            Some("gltf") => Self::Gltf,
+            _ => Self::Obj,
+        }
+    }
+
+    pub fn write(self, surface: &Isosurface) -> Result<String, Error> {
+        match self {
+            Self::Obj => write_obj(surface),
+            Self::Ply => write_ply(surface),
+            Self::Stl => write_stl(surface),
+            Self::Gltf => write_gltf(surface),
+        }
+    }
+}
+
+fn title(surface: &Isosurface) -> String {
+    format!("mo-draw isosurface of MO {n} at +-{iso}", n = surface.mo_idx + 1, iso = surface.iso)
+}
+
+// one object per phase, with indices running on across both
+fn write_obj(surface: &Isosurface) -> Result<String, Error> {
+    let mut s = String::new();
+    let mut offset = 1;
+
+    writeln!(s, "# {}", title(surface))?;
+
+    for (name, mesh, _) in surface.phases() {
+        writeln!(s, "o {name}")?;
+
+        for [x, y, z] in &mesh.positions {
+            writeln!(s, "v {x:.6} {y:.6} {z:.6}")?;
+        }
+
+        for [x, y, z] in &mesh.normals {
+            writeln!(s, "vn {x:.6} {y:.6} {z:.6}")?;
+        }
+
+        for tri in &mesh.triangles {
+            let [a, b, c] = tri.map(|v| v + offset);
+
+            writeln!(s, "f {a}//{a} {b}//{b} {c}//{c}")?;
+        }
+
+        offset += mesh.positions.len() as u32;
+    }
+
+    Ok(s)
+}
+
+// both phases in one mesh, told apart by their vertex colours
+fn write_ply(surface: &Isosurface) -> Result<String, Error> {
+    let mut s = String::new();
+    let phases = surface.phases();
+    let num_vertices = phases.iter().map(|(_, m, _)| m.positions.len()).sum::<usize>();
+    let num_faces = phases.iter().map(|(_, m, _)| m.triangles.len()).sum::<usize>();
+
+    writeln!(s, "ply")?;
+    writeln!(s, "format ascii 1.0")?;
+    writeln!(s, "comment {}", title(surface))?;
+    writeln!(s, "element vertex {num_vertices}")?;
+
+    for property in ["x", "y", "z", "nx", "ny", "nz"] {
+        writeln!(s, "property float {property}")?;
+    }
+
+    for property in ["red", "green", "blue"] {
+        writeln!(s, "property uchar {property}")?;
+    }
+
+    writeln!(s, "element face {num_faces}")?;
+    writeln!(s, "property list uchar int vertex_indices")?;
+    writeln!(s, "end_header")?;
+
+    for (_, mesh, color) in phases {
+        let [r, g, b] = color.map(|c| (c * 255.0).round() as u8);
+
+        for (p, n) in mesh.positions.iter().zip(&mesh.normals) {
+            writeln!(s, "{:.6} {:.6} {:.6} {:.6} {:.6} {:.6} {r} {g} {b}",
+                     p[0], p[1], p[2], n[0], n[1], n[2])?;
+        }
+    }
+
+    let mut offset = 0;
+
+    for (_, mesh, _) in phases {
+        for tri in &mesh.triangles {
+            let [a, b, c] = tri.map(|v| v + offset);
+
+            writeln!(s, "3 {a} {b} {c}")?;
+        }
+
+        offset += mesh.positions.len() as u32;
+    }
+
+    Ok(s)
+}
+
+// a solid per phase, with facet normals from the winding
+fn write_stl(surface: &Isosurface) -> Result<String, Error> {
+    let mut s = String::new();
+
+    for (name, mesh, _) in surface.phases() {
+        writeln!(s, "solid {name}")?;
+
+        for tri in &mesh.triangles {
+            let [a, b, c] = tri.map(|v| mesh.positions[v as usize]);
+            let face = cross(sub(b, a), sub(c, a));
+            let [nx, ny, nz] = face.map(|x| x / dot(face, face).sqrt());
+
+            writeln!(s, "  facet normal {nx:.6e} {ny:.6e} {nz:.6e}")?;
+            writeln!(s, "    outer loop")?;
+
+            for [x, y, z] in [a, b, c] {
+                writeln!(s, "      vertex {x:.6e} {y:.6e} {z:.6e}")?;
+            }
+
+            writeln!(s, "    endloop")?;
+            writeln!(s, "  endfacet")?;
+        }
+
+        writeln!(s, "endsolid {name}")?;
+    }
+
+    Ok(s)
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut s = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter()
+                     .enumerate()
+                     .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                s.push(CHARS[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                s.push('=');
+            }
+        }
+    }
+
+    s
+}
+
+// a self-contained .gltf, with its buffer inlined as a data uri and a mesh
+// and material per phase; phases with no triangles are left out
+fn write_gltf(surface: &Isosurface) -> Result<String, Error> {
+    const ARRAY_BUFFER: u32 = 34962;
+    const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+    const FLOAT: u32 = 5126;
+    const UNSIGNED_INT: u32 = 5125;
+
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut views = Vec::new();
+    let mut accessors = Vec::new();
+    let mut materials = Vec::new();
+    let mut meshes = Vec::new();
+
+    let mut push_view = |buffer: &mut Vec<u8>, bytes: Vec<u8>, target: u32| {
+        views.push(json!({
+            "buffer": 0,
+            "byteOffset": buffer.len(),
+            "byteLength": bytes.len(),
+            "target": target,
+        }));
+        buffer.extend(bytes);
+
+        views.len() - 1
+    };
+
+    for (name, mesh, color) in surface.phases() {
+        if mesh.triangles.is_empty() {
+            continue;
+        }
+
+        let floats = |vs: &[[f64; 3]]| vs.iter()
+                                         .flatten()
+                                         .flat_map(|&x| (x as f32).to_le_bytes())
+                                         .collect::<Vec<u8>>();
+        let lo = (0..3).map(|i| mesh.positions.iter().map(|p| p[i] as f32).fold(f32::INFINITY, f32::min))
+                       .collect::<Vec<_>>();
+        let hi = (0..3).map(|i| mesh.positions.iter().map(|p| p[i] as f32).fold(f32::NEG_INFINITY, f32::max))
+                       .collect::<Vec<_>>();
+        let indices = mesh.triangles
+                          .iter()
+                          .flatten()
+                          .flat_map(|i| i.to_le_bytes())
+                          .collect::<Vec<u8>>();
+
+        let position_view = push_view(&mut buffer, floats(&mesh.positions), ARRAY_BUFFER);
+        let normal_view = push_view(&mut buffer, floats(&mesh.normals), ARRAY_BUFFER);
+        let index_view = push_view(&mut buffer, indices, ELEMENT_ARRAY_BUFFER);
+
+        accessors.push(json!({
+            "bufferView": position_view,
+            "componentType": FLOAT,
+            "count": mesh.positions.len(),
+            "type": "VEC3",
+            "min": lo,
+            "max": hi,
+        }));
+        accessors.push(json!({
+            "bufferView": normal_view,
+            "componentType": FLOAT,
+            "count": mesh.normals.len(),
+            "type": "VEC3",
+        }));
+        accessors.push(json!({
+            "bufferView": index_view,
+            "componentType": UNSIGNED_INT,
+            "count": 3 * mesh.triangles.len(),
+            "type": "SCALAR",
+        }));
+
+        let [r, g, b] = color;
+
+        materials.push(json!({
+            "name": name,
+            "pbrMetallicRoughness": {
+                "baseColorFactor": [r, g, b, 1.0],
+                "metallicFactor": 0.0,
+                "roughnessFactor": 0.6,
+            },
+        }));
+        meshes.push(json!({
+            "name": name,
+            "primitives": [{
+                "attributes": {
+                    "POSITION": accessors.len() - 3,
+                    "NORMAL": accessors.len() - 2,
+                },
+                "indices": accessors.len() - 1,
+                "material": materials.len() - 1,
+            }],
+        }));
+    }
+
+    let nodes = (0..meshes.len()).map(|i| json!({ "mesh": i }))
+                                 .collect::<Vec<_>>();
+    let mut gltf = json!({
+        "asset": { "version": "2.0", "generator": title(surface) },
+        "scene": 0,
+        "scenes": [{ "nodes": (0..nodes.len()).collect::<Vec<_>>() }],
+        "nodes": nodes,
+        "meshes": meshes,
+        "materials": materials,
+        "accessors": accessors,
+        "bufferViews": views,
+    });
+
+    // gltf won't have an empty buffer
+    if !buffer.is_empty() {
+        gltf["buffers"] = json!([{
+            "byteLength": buffer.len(),
+            "uri": format!("data:application/octet-stream;base64,{}", base64(&buffer)),
+        }]);
+    }
+
+    Ok(serde_json::to_string_pretty(&gltf)?)
+}
@@ -1,6 +1,23 @@
 use ndarray::{ Array2 };
+use serde::Deserialize;
 use crate::error::Error;
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Spin {
+    #[default]
+    Alpha,
+    Beta,
+}
+
+// whatever the input knows about each mo besides its coefficients
+#[derive(Debug, Default, Clone)]
+pub struct MoInfo {
+    pub energy: Option<f64>,
+    pub occ:    Option<f64>,
+    pub spin:   Spin,
+}
+
 fn argvs_shape(argvs: &Vec<Vec<String>>) -> Result<(usize, usize), Error> {
     let height = argvs.len();
     let width = argvs.first()
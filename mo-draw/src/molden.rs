@@ -0,0 +1,362 @@
+use ndarray::{ Array1, Array2 };
+use crate::{
+    atom::{ self, Atom },
+    basis::{ Basis, Shell, ShellNorm },
+    error::Error,
+    mo_coefs::{ MoInfo, Spin },
+    Orbitals, Params,
+};
+
+struct Section<'a> {
+    name:   String,
+    args:   String,
+    lines:  Vec<&'a str>,
+}
+
+fn split_sections(text: &str) -> Vec<Section<'_>> {
+    let mut sections: Vec<Section> = Vec::new();
+
+    for line in text.lines() {
+        let line = line.trim();
+
+        if let Some(rest) = line.strip_prefix('[') {
+            let (name, args) = rest.split_once(']')
+                                   .unwrap_or((rest, ""));
+
+            sections.push(Section {
+                name:   name.trim().to_lowercase(),
+                args:   args.trim().to_lowercase(),
+                lines:  Vec::new(),
+            });
+        } else if let Some(section) = sections.last_mut() {
+            if !line.is_empty() {
+                section.lines.push(line);
+            }
+        }
+    }
+
+    sections
+}
+
+// molden files come out of fortran often enough to see 1.0D+00
+fn parse_f64(s: &str) -> Result<f64, Error> {
+    Ok(s.replace(['D', 'd'], "E")
+        .parse()?)
+}
+
+fn parse_atoms(section: &Section) -> Result<Vec<(u32, [f64; 3])>, Error> {
+    let scale = match section.args.as_str() {
+        "" | "au" => 1.0,
+        "angs" => atom::ANGSTROM,
+        _ => return Err("invalid molden atom units".into()),
+    };
+    let mut atoms = Vec::new();
+
+    for line in &section.lines {
+        let fields = line.split_whitespace()
+                         .collect::<Vec<_>>();
+
+        if fields.len() != 6 {
+            return Err("invalid molden atom line".into());
+        }
+
+        let atomic_num = fields[2].parse()?;
+        let mut position = [0.0; 3];
+
+        for i in 0..3 {
+            position[i] = parse_f64(fields[3 + i])? * scale;
+        }
+
+        atoms.push((atomic_num, position));
+    }
+
+    Ok(atoms)
+}
+
+fn parse_gto(section: &Section, num_atoms: usize) -> Result<Vec<Vec<Shell>>, Error> {
+    let mut shellses = vec![Vec::new(); num_atoms];
+    let mut atom_idx = None;
+    let mut lines = section.lines.iter();
+
+    while let Some(line) = lines.next() {
+        let fields = line.split_whitespace()
+                         .collect::<Vec<_>>();
+
+        if fields[0].chars().all(|c| c.is_ascii_digit()) {
+            let i: usize = fields[0].parse()?;
+
+            if i < 1 || i > num_atoms {
+                return Err("invalid molden gto atom index".into());
+            }
+
+            atom_idx = Some(i - 1);
+            continue;
+        }
+
+        let shells: &mut Vec<Shell> = match atom_idx {
+            Some(i) => &mut shellses[i],
+            None => return Err("molden gto shell before atom".into()),
+        };
+
+        if fields.len() < 2 {
+            return Err("invalid molden gto shell line".into());
+        }
+
+        let label = fields[0].to_lowercase();
+        let num_prims: usize = fields[1].parse()?;
+        let mut expns = Vec::new();
+        let mut coefs = Vec::new();
+        let mut sp_coefs = Vec::new();
+
+        for _ in 0..num_prims {
+            let fields = lines.next()
+                              .ok_or("truncated molden gto shell")?
+                              .split_whitespace()
+                              .collect::<Vec<_>>();
+
+            expns.push(parse_f64(fields[0])?);
+            coefs.push(parse_f64(fields.get(1).ok_or("missing molden gto coefficient")?)?);
+
+            if label == "sp" {
+                sp_coefs.push(parse_f64(fields.get(2).ok_or("missing molden sp coefficient")?)?);
+            }
+        }
+
+        let l = match label.as_str() {
+            "sp" => {
+                shells.extend(Shell::split_sp(expns, coefs, sp_coefs));
+                continue;
+            },
+            "s" => 0,
+            "p" => 1,
+            "d" => 2,
+            "f" => 3,
+            "g" => 4,
+            _ => return Err(format!("unsupported molden shell '{label}'").into()),
+        };
+
+        shells.push(Shell { l, cart: false, expns, coefs });
+    }
+
+    Ok(shellses)
+}
+
+struct Mo {
+    info:   MoInfo,
+    coefs:  Vec<(usize, f64)>,
+}
+
+fn parse_mos(section: &Section) -> Result<Vec<Mo>, Error> {
+    let mut mos: Vec<Mo> = Vec::new();
+    let mut in_coefs = true;
+
+    for line in &section.lines {
+        if let Some((key, value)) = line.split_once('=') {
+            if in_coefs {
+                mos.push(Mo { info: MoInfo::default(), coefs: Vec::new() });
+                in_coefs = false;
+            }
+
+            let info = &mut mos.last_mut()
+                               .unwrap()
+                               .info;
+            let value = value.trim();
+
+            match key.trim().to_lowercase().as_str() {
+                "ene" => info.energy = Some(parse_f64(value)?),
+                "occup" => info.occ = Some(parse_f64(value)?),
+                "spin" => info.spin = match value.to_lowercase().as_str() {
+                    "alpha" => Spin::Alpha,
+                    "beta" => Spin::Beta,
+                    _ => return Err("invalid molden mo spin".into()),
+                },
+                _ => (),
+            }
+        } else {
+            let mo = mos.last_mut()
+                        .ok_or("molden mo coefficient before header")?;
+            let (idx, coef) = line.split_once(char::is_whitespace)
+                                  .ok_or("invalid molden mo coefficient line")?;
+
+            mo.coefs.push((idx.parse()?, parse_f64(coef.trim())?));
+            in_coefs = true;
+        }
+    }
+
+    Ok(mos)
+}
+
+pub fn parse(text: &str) -> Result<Params, Error> {
+    let sections = split_sections(text);
+    let find = |name: &str| {
+        sections.iter()
+                .find(|s| s.name == name)
+                .ok_or(format!("molden file has no [{name}] section"))
+    };
+
+    // molden means cartesian unless told otherwise
+    let mut pure = [true, true, false, false, false];
+
+    for section in &sections {
+        match section.name.as_str() {
+            "5d" | "5d7f" => { pure[2] = true; pure[3] = true; },
+            "5d10f" => pure[2] = true,
+            "7f" => pure[3] = true,
+            "9g" => pure[4] = true,
+            _ => (),
+        }
+    }
+
+    let atoms = parse_atoms(find("atoms")?)?;
+    let mut shellses = parse_gto(find("gto")?, atoms.len())?;
+    let mos = parse_mos(find("mo")?)?;
+
+    let mut num_ao = 0;
+
+    for shell in shellses.iter_mut().flatten() {
+        shell.cart = !pure[shell.l as usize];
+        num_ao += shell.num_aos();
+    }
+
+    let bases = shellses.iter()
+                        .map(|shells| Basis::from_shells(shells, ShellNorm::Contracted))
+                        .collect::<Result<Array1<_>, _>>()?;
+
+    let atoms = atoms.into_iter()
+                     .enumerate()
+                     .map(|(i, (atomic_num, position))| {
+                         Atom { basis_id: i, atomic_num, position }
+                     })
+                     .collect();
+
+    let mut mo_coefs = Array2::zeros((mos.len(), num_ao));
+
+    // writers are allowed to leave out zero coefficients
+    for (i, mo) in mos.iter().enumerate() {
+        for &(idx, coef) in &mo.coefs {
+            if idx < 1 || idx > num_ao {
+                return Err("invalid molden mo coefficient index".into());
+            }
+
+            mo_coefs[(i, idx - 1)] = coef;
+        }
+    }
+
+    let mo_info = mos.into_iter()
+                     .map(|mo| mo.info)
+                     .collect();
+
+    Ok(Params {
+        atoms,
+        orbitals:   Orbitals::Contracted(bases),
+        mo_coefs,
+        mo_info,
+        settings:   Default::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOLDEN: &str = "\
+[Molden Format]
+[Atoms] Angs
+O 1 8 0.0 0.0 0.0
+H 2 1 0.0 0.0 1.0
+[GTO]
+1 0
+s 1 1.00
+130.7093 1.0
+sp 2 1.00
+5.0331513D+00 -0.09996723 0.15591627
+1.1695961D+00 0.39951283 0.60768372
+d 1 1.00
+0.8 1.0
+f 1 1.00
+0.6 1.0
+
+2 0
+s 1 1.00
+3.42525091 0.5
+[MO]
+Sym= A1
+Ene= -20.25
+Spin= Alpha
+Occup= 2.0
+ 1 0.99
+ 3 0.25
+Sym= A1
+Ene= 0.5D+00
+Spin= Beta
+Occup= 0.0
+ 18 1.0
+";
+
+    fn bases(params: &Params) -> &Array1<Basis> {
+        match &params.orbitals {
+            Orbitals::Contracted(bases) => bases,
+            _ => panic!("molden input gave primitive orbitals"),
+        }
+    }
+
+    fn shells(params: &Params) -> Vec<(i32, bool, usize)> {
+        bases(params).iter()
+                     .flat_map(|b| &b.shells)
+                     .map(|s| (s.l, s.cart, s.expns.len()))
+                     .collect()
+    }
+
+    #[test]
+    fn reads_atoms_shells_and_mos() {
+        let params = parse(MOLDEN).unwrap();
+
+        assert_eq!(params.atoms.iter().map(|a| a.atomic_num).collect::<Vec<_>>(), [8, 1]);
+        assert!((params.atoms[1].position[2] - atom::ANGSTROM).abs() < 1e-12);
+        // sp splits into an s and a p sharing exponents, and d and f are
+        // cartesian without a flag
+        assert_eq!(shells(&params), [
+            (0, false, 1), (0, false, 2), (1, false, 2), (2, true, 1), (3, true, 1),
+            (0, false, 1),
+        ]);
+        assert_eq!(bases(&params)[0].shells[2].expns, [5.0331513, 1.1695961]);
+
+        // contractions come out normalized, however they were written
+        for shell in bases(&params).iter().flat_map(|b| &b.shells) {
+            assert!((shell.contraction_norm() - 1.0).abs() < 1e-12);
+        }
+
+        assert_eq!(params.mo_coefs.dim(), (2, 1 + 1 + 3 + 6 + 10 + 1));
+        // coefficients left out are zero
+        assert_eq!(params.mo_coefs.row(0).iter().filter(|&&c| c != 0.0).count(), 2);
+        assert_eq!(params.mo_coefs[(0, 2)], 0.25);
+        assert_eq!(params.mo_coefs[(1, 17)], 1.0);
+        assert_eq!(params.mo_info[1].energy, Some(0.5));
+        assert_eq!(params.mo_info[1].occ, Some(0.0));
+        assert_eq!(params.mo_info[1].spin, Spin::Beta);
+    }
+
+    #[test]
+    fn flags_make_shells_pure() {
+        let cart = |flag: &str| {
+            let params = parse(&MOLDEN.replace("[GTO]", &format!("{flag}\n[GTO]"))).unwrap();
+            let shells = shells(&params);
+
+            (shells[3].1, shells[4].1, params.mo_coefs.ncols())
+        };
+
+        assert_eq!(cart("[5D]"), (false, false, 1 + 1 + 3 + 5 + 7 + 1));
+        assert_eq!(cart("[5D7F]"), (false, false, 18));
+        assert_eq!(cart("[5D10F]"), (false, true, 1 + 1 + 3 + 5 + 10 + 1));
+        assert_eq!(cart("[7F]"), (true, false, 1 + 1 + 3 + 6 + 7 + 1));
+        assert_eq!(cart("[9G]"), (true, true, 22));
+    }
+
+    #[test]
+    fn rejects_coefficients_past_the_aos() {
+        let text = MOLDEN.replace("[Atoms]", "[5D7F]\n[Atoms]")
+                         .replace(" 18 1.0", " 19 1.0");
+
+        assert!(parse(&text).is_err());
+    }
+}
@@ -0,0 +1,499 @@
+use ndarray::{ Array1, Array2 };
+use crate::{
+    atom::{ self, Atom },
+    drawer::Settings,
+    element,
+    error::Error,
+    Orbitals, Params,
+};
+
+#[derive(Debug, Clone)]
+pub struct MolAtom {
+    pub symbol:     String,
+    // zero for query atoms and anything else that isn't an element
+    pub atomic_num: u32,
+    // in angstrom, as written
+    pub position:   [f64; 3],
+    pub charge:     i32,
+    // 0 none, 1 singlet, 2 doublet, 3 triplet
+    pub radical:    u32,
+    // the mass number, if it isn't the natural mix; v2000's atom block only
+    // has differences from a mass table, so those are left out
+    pub isotope:    Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BondType {
+    Single,
+    Double,
+    Triple,
+    Aromatic,
+    Other(u32),
+}
+
+impl BondType {
+    fn from_code(code: u32) -> Self {
+        match code {
+            1 => Self::Single,
+            2 => Self::Double,
+            3 => Self::Triple,
+            4 => Self::Aromatic,
+            _ => Self::Other(code),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Bond {
+    // zero-based atom indices
+    pub atoms:  [usize; 2],
+    pub ty:     BondType,
+    pub stereo: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Molfile {
+    pub title:      String,
+    pub program:    String,
+    pub comment:    String,
+    pub atoms:      Vec<MolAtom>,
+    pub bonds:      Vec<Bond>,
+    // sdf data items, in the order they appear
+    pub data:       Vec<(String, String)>,
+}
+
+impl Molfile {
+    pub fn to_atoms(&self) -> Array1<Atom> {
+        self.atoms.iter()
+                  .enumerate()
+                  .map(|(i, a)| Atom {
+                      basis_id:   i,
+                      atomic_num: a.atomic_num,
+                      position:   a.position.map(|x| x * atom::ANGSTROM),
+                  })
+                  .collect()
+    }
+
+    // just the geometry, with neither a basis nor any mos; each atom gets
+    // its own basis id, so bases given alongside go one per atom in order
+    pub fn to_params(&self) -> Params {
+        Params {
+            atoms:      self.to_atoms(),
+            orbitals:   Orbitals::Contracted(Array1::from_vec(Vec::new())),
+            mo_coefs:   Array2::zeros((0, 0)),
+            mo_info:    Array1::from_vec(Vec::new()),
+            settings:   Settings::default(),
+        }
+    }
+}
+
+fn column(line: &str, start: usize, end: usize) -> &str {
+    let end = end.min(line.len());
+
+    line.get(start.min(end)..end)
+        .unwrap_or("")
+        .trim()
+}
+
+fn parse_or<T: std::str::FromStr>(s: &str, default: T) -> Result<T, Error>
+where
+    T::Err: std::error::Error + 'static,
+{
+    if s.is_empty() {
+        Ok(default)
+    } else {
+        Ok(s.parse()?)
+    }
+}
+
+fn new_atom(symbol: &str, position: [f64; 3]) -> MolAtom {
+    MolAtom {
+        symbol:     symbol.to_string(),
+        atomic_num: element::atomic_num(symbol).unwrap_or(0),
+        position,
+        charge:     0,
+        radical:    0,
+        isotope:    None,
+    }
+}
+
+// the v2000 property lines that stand in for atom block values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Property {
+    Charge,
+    Radical,
+    Isotope,
+}
+
+impl Property {
+    // none puts it back to the default
+    fn set(self, atom: &mut MolAtom, value: Option<&str>) -> Result<(), Error> {
+        match self {
+            Self::Charge => atom.charge = value.map_or(Ok(0), str::parse)?,
+            Self::Radical => atom.radical = value.map_or(Ok(0), str::parse)?,
+            Self::Isotope => atom.isotope = value.map(str::parse).transpose()?,
+        }
+
+        Ok(())
+    }
+}
+
+fn atom_index(s: &str, num_atoms: usize) -> Result<usize, Error> {
+    let i: usize = s.parse()?;
+
+    if i < 1 || i > num_atoms {
+        return Err("molfile bond refers to a missing atom".into());
+    }
+
+    Ok(i - 1)
+}
+
+fn parse_v2000<'a>(mol: &mut Molfile,
+                   counts: &str,
+                   lines: &mut impl Iterator<Item = &'a str>) -> Result<(), Error> {
+    let num_atoms: usize = parse_or(column(counts, 0, 3), 0)?;
+    let num_bonds: usize = parse_or(column(counts, 3, 6), 0)?;
+
+    for _ in 0..num_atoms {
+        let line = lines.next()
+                        .ok_or("truncated molfile atom block")?;
+        let position = [
+            column(line, 0, 10).parse()?,
+            column(line, 10, 20).parse()?,
+            column(line, 20, 30).parse()?,
+        ];
+        let mut atom = new_atom(column(line, 31, 34), position);
+
+        match parse_or(column(line, 36, 39), 0)? {
+            0 => (),
+            4 => atom.radical = 2,
+            code @ 1..=7 => atom.charge = 4 - code,
+            _ => return Err("invalid molfile atom charge".into()),
+        }
+
+        mol.atoms.push(atom);
+    }
+
+    for _ in 0..num_bonds {
+        let line = lines.next()
+                        .ok_or("truncated molfile bond block")?;
+
+        mol.bonds.push(Bond {
+            atoms:  [
+                atom_index(column(line, 0, 3), num_atoms)?,
+                atom_index(column(line, 3, 6), num_atoms)?,
+            ],
+            ty:     BondType::from_code(parse_or(column(line, 6, 9), 0)?),
+            stereo: parse_or(column(line, 9, 12), 0)?,
+        });
+    }
+
+    // a single chg, rad or iso line overrides every value from the atom block
+    let mut seen = Vec::new();
+
+    for line in lines {
+        if line.starts_with("M  END") {
+            break;
+        }
+
+        let fields = line.split_whitespace()
+                         .collect::<Vec<_>>();
+
+        let property = match fields.get(1) {
+            Some(&"CHG") if fields[0] == "M" => Property::Charge,
+            Some(&"RAD") if fields[0] == "M" => Property::Radical,
+            Some(&"ISO") if fields[0] == "M" => Property::Isotope,
+            _ => continue,
+        };
+
+        if !seen.contains(&property) {
+            for atom in &mut mol.atoms {
+                property.set(atom, None)?;
+            }
+
+            seen.push(property);
+        }
+
+        let count: usize = fields.get(2)
+                                 .ok_or("invalid molfile property line")?
+                                 .parse()?;
+
+        if fields.len() < 3 + 2 * count {
+            return Err("truncated molfile property line".into());
+        }
+
+        for pair in fields[3..(3 + 2 * count)].chunks(2) {
+            let atom = &mut mol.atoms[atom_index(pair[0], num_atoms)?];
+
+            property.set(atom, Some(pair[1]))?;
+        }
+    }
+
+    Ok(())
+}
+
+// v3000 lines may be continued with a trailing '-'
+fn v30_lines<'a>(lines: &mut impl Iterator<Item = &'a str>) -> Result<Vec<String>, Error> {
+    let mut v30 = Vec::new();
+    let mut pending = String::new();
+
+    for line in lines {
+        if line.starts_with("M  END") {
+            return Ok(v30);
+        }
+
+        let Some(rest) = line.strip_prefix("M  V30 ") else {
+            continue;
+        };
+
+        match rest.strip_suffix('-') {
+            Some(rest) => pending.push_str(rest),
+            None => {
+                pending.push_str(rest);
+                v30.push(std::mem::take(&mut pending));
+            },
+        }
+    }
+
+    Err("molfile has no M  END line".into())
+}
+
+fn parse_v3000<'a>(mol: &mut Molfile,
+                   lines: &mut impl Iterator<Item = &'a str>) -> Result<(), Error> {
+    enum Block { None, Atom, Bond }
+
+    let mut block = Block::None;
+    let mut bond_fields = Vec::new();
+
+    for line in v30_lines(lines)? {
+        let fields = line.split_whitespace()
+                         .collect::<Vec<_>>();
+
+        match fields.as_slice() {
+            ["BEGIN", "ATOM"] => block = Block::Atom,
+            ["BEGIN", "BOND"] => block = Block::Bond,
+            ["END", _] => block = Block::None,
+            [_, symbol, x, y, z, _, props @ ..] if matches!(block, Block::Atom) => {
+                let mut atom = new_atom(symbol, [x.parse()?, y.parse()?, z.parse()?]);
+
+                for prop in props {
+                    match prop.split_once('=') {
+                        Some(("CHG", v)) => atom.charge = v.parse()?,
+                        Some(("RAD", v)) => atom.radical = v.parse()?,
+                        Some(("MASS", v)) => atom.isotope = Some(v.parse()?),
+                        _ => (),
+                    }
+                }
+
+                mol.atoms.push(atom);
+            },
+            [_, ty, a, b, props @ ..] if matches!(block, Block::Bond) => {
+                let stereo = props.iter()
+                                  .find_map(|p| p.strip_prefix("CFG="))
+                                  .map(|v| v.parse())
+                                  .transpose()?
+                                  .unwrap_or(0);
+
+                // atoms might not all be read yet if blocks come out of order
+                bond_fields.push((ty.parse()?, a.to_string(), b.to_string(), stereo));
+            },
+            _ => (),
+        }
+    }
+
+    for (ty, a, b, stereo) in bond_fields {
+        mol.bonds.push(Bond {
+            atoms:  [atom_index(&a, mol.atoms.len())?, atom_index(&b, mol.atoms.len())?],
+            ty:     BondType::from_code(ty),
+            stereo,
+        });
+    }
+
+    Ok(())
+}
+
+fn parse_record(record: &[&str]) -> Result<Molfile, Error> {
+    if record.len() < 4 {
+        return Err("truncated molfile header".into());
+    }
+
+    let mut mol = Molfile {
+        title:      record[0].trim().to_string(),
+        program:    column(record[1], 2, 10).to_string(),
+        comment:    record[2].trim().to_string(),
+        ..Default::default()
+    };
+
+    let counts = record[3];
+    let mut lines = record[4..].iter()
+                               .copied();
+
+    match column(counts, 33, 39) {
+        "V3000" => parse_v3000(&mut mol, &mut lines)?,
+        "V2000" | "" => parse_v2000(&mut mol, counts, &mut lines)?,
+        _ => return Err("unsupported molfile version".into()),
+    }
+
+    // whatever follows the ctab in an sdf record is "> <name>" data items,
+    // each running until a blank line
+    while let Some(line) = lines.next() {
+        let Some(header) = line.strip_prefix('>') else {
+            continue;
+        };
+
+        let name = header.split_once('<')
+                         .and_then(|(_, rest)| rest.split_once('>'))
+                         .map(|(name, _)| name.to_string())
+                         .unwrap_or_default();
+        let value = lines.by_ref()
+                         .take_while(|l| !l.trim().is_empty())
+                         .collect::<Vec<_>>()
+                         .join("\n");
+
+        mol.data.push((name, value));
+    }
+
+    Ok(mol)
+}
+
+pub fn parse(text: &str) -> Result<Molfile, Error> {
+    let lines = text.lines()
+                    .collect::<Vec<_>>();
+    let end = lines.iter()
+                   .position(|l| l.trim_end() == "$$$$")
+                   .unwrap_or(lines.len());
+
+    parse_record(&lines[..end])
+}
+
+pub fn parse_sdf(text: &str) -> Result<Vec<Molfile>, Error> {
+    let lines = text.lines()
+                    .collect::<Vec<_>>();
+
+    lines.split(|l| l.trim_end() == "$$$$")
+         .filter(|record| record.iter().any(|l| !l.trim().is_empty()))
+         .map(parse_record)
+         .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input::Format;
+    use super::*;
+
+    const V2000: &str = "\
+water
+  mo-draw
+
+  3  2  0  0  0  0  0  0  0  0999 V2000
+    0.0000    0.0000    0.1173 O   0  5  0  0  0  0  0  0  0  0  0  0
+    0.0000    0.7572   -0.4692 H   0  0  0  0  0  0  0  0  0  0  0  0
+    0.0000   -0.7572   -0.4692 H   0  0  0  0  0  0  0  0  0  0  0  0
+  1  2  1  0
+  1  3  2  6
+M  END
+";
+
+    const V3000: &str = "\
+water
+  mo-draw
+
+  0  0  0     0  0            999 V3000
+M  V30 BEGIN CTAB
+M  V30 COUNTS 3 2 0 0 0
+M  V30 BEGIN ATOM
+M  V30 1 O 0.0 0.0 0.1173 0 CHG=-1 -
+M  V30 MASS=18
+M  V30 2 H 0.0 0.7572 -0.4692 0
+M  V30 3 H 0.0 -0.7572 -0.4692 0 RAD=2
+M  V30 END ATOM
+M  V30 BEGIN BOND
+M  V30 1 1 1 2
+M  V30 2 2 1 3 CFG=1
+M  V30 END BOND
+M  V30 END CTAB
+M  END
+";
+
+    fn symbols(mol: &Molfile) -> Vec<&str> {
+        mol.atoms.iter()
+                 .map(|a| a.symbol.as_str())
+                 .collect()
+    }
+
+    #[test]
+    fn reads_v2000_atoms_and_bonds() {
+        let mol = parse(V2000).unwrap();
+
+        assert_eq!(mol.title, "water");
+        assert_eq!(mol.program, "mo-draw");
+        assert_eq!(symbols(&mol), ["O", "H", "H"]);
+        assert_eq!(mol.atoms.iter().map(|a| a.atomic_num).collect::<Vec<_>>(), [8, 1, 1]);
+        assert_eq!(mol.atoms[1].position, [0.0, 0.7572, -0.4692]);
+        // charge code 5 is -1
+        assert_eq!(mol.atoms[0].charge, -1);
+        assert_eq!(mol.bonds.len(), 2);
+        assert_eq!(mol.bonds[1].atoms, [0, 2]);
+        assert_eq!(mol.bonds[1].ty, BondType::Double);
+        assert_eq!(mol.bonds[1].stereo, 6);
+    }
+
+    #[test]
+    fn reads_v3000_atoms_and_bonds() {
+        let mol = parse(V3000).unwrap();
+        let v2000 = parse(V2000).unwrap();
+
+        assert_eq!(symbols(&mol), ["O", "H", "H"]);
+
+        for (a, b) in mol.atoms.iter().zip(&v2000.atoms) {
+            assert_eq!(a.position, b.position);
+        }
+
+        // the first atom's properties carry over onto a continuation line
+        assert_eq!(mol.atoms[0].charge, -1);
+        assert_eq!(mol.atoms[0].isotope, Some(18));
+        assert_eq!(mol.atoms[2].radical, 2);
+        assert_eq!(mol.bonds[1].atoms, [0, 2]);
+        assert_eq!(mol.bonds[1].ty, BondType::Double);
+        assert_eq!(mol.bonds[1].stereo, 1);
+    }
+
+    #[test]
+    fn property_lines_override_the_atom_block() {
+        let text = V2000.replace("M  END", "\
+M  CHG  1   2   1
+M  ISO  2   1  17   3   2
+M  END");
+        let mol = parse(&text).unwrap();
+
+        // the charge from the atom block is dropped, not kept alongside
+        assert_eq!(mol.atoms.iter().map(|a| a.charge).collect::<Vec<_>>(), [0, 1, 0]);
+        assert_eq!(mol.atoms.iter().map(|a| a.isotope).collect::<Vec<_>>(),
+                   [Some(17), None, Some(2)]);
+    }
+
+    #[test]
+    fn sdf_files_give_their_first_record() {
+        let hydrogen = "\
+hydrogen
+  mo-draw
+
+  2  1  0  0  0  0  0  0  0  0999 V2000
+    0.0000    0.0000    0.0000 H   0  0
+    0.7400    0.0000    0.0000 H   0  0
+  1  2  1  0
+M  END
+";
+        let text = format!("{V2000}> <name>\nwater\n\n$$$$\n{hydrogen}$$$$\n");
+        let records = parse_sdf(&text).unwrap();
+
+        assert_eq!(records.iter().map(|r| r.title.as_str()).collect::<Vec<_>>(),
+                   ["water", "hydrogen"]);
+        assert_eq!(records[0].data, [("name".to_string(), "water".to_string())]);
+        assert_eq!(Format::Sdf.parse(&text).unwrap().atoms.len(), 3);
+
+        let text = format!("{hydrogen}$$$$\n{V2000}$$$$\n");
+
+        assert_eq!(Format::Sdf.parse(&text).unwrap().atoms.len(), 2);
+    }
+}
@@ -0,0 +1,170 @@
+use std::{
+    fs::File,
+    io::BufWriter,
+};
+#[cfg(egl_backend)]
+use glutin::{
+    api::egl::{
+        context::PossiblyCurrentContext,
+        device::Device,
+        display::Display as EglDisplay,
+    },
+    config::{ ConfigSurfaceTypes, ConfigTemplateBuilder },
+    context::{ ContextApi, ContextAttributesBuilder },
+    display::Display,
+    prelude::*,
+};
+#[cfg(egl_backend)]
+use crate::drawer::{ Drawer, Framebuffer };
+use crate::{
+    drawer::{ CpuDrawer, Filter, RenderMode, Renderer },
+    error::Error,
+    Params,
+};
+
+// a gl context with no window or surface behind it, current on this thread
+// for as long as it's kept around
+#[cfg(egl_backend)]
+struct Headless {
+    display:    Display,
+    _context:   PossiblyCurrentContext,
+}
+
+#[cfg(egl_backend)]
+impl Headless {
+    // takes the first device that gives a context, which on machines without
+    // a gpu is mesa's llvmpipe
+    fn new() -> Result<Self, Error> {
+        let mut last_err: Error = "no egl devices to render with".into();
+
+        for device in Device::query_devices()? {
+            match Self::on_device(&device) {
+                Ok(headless) => return Ok(headless),
+                Err(err) => last_err = err,
+            }
+        }
+
+        Err(last_err)
+    }
+
+    fn on_device(device: &Device) -> Result<Self, Error> {
+        let display = unsafe { EglDisplay::with_device(device, None)? };
+        // nothing gets drawn to anything but a framebuffer object
+        let template = ConfigTemplateBuilder::new()
+                                             .with_alpha_size(8)
+                                             .with_surface_type(ConfigSurfaceTypes::empty())
+                                             .build();
+        let config = unsafe {
+            display.find_configs(template)?
+                   .next()
+                   .ok_or("no usable egl config")?
+        };
+
+        let ctx_attrs =
+            ContextAttributesBuilder::new()
+                                     .build(None);
+        let fallback_ctx_attrs =
+            ContextAttributesBuilder::new()
+                                     .with_context_api(ContextApi::Gles(None))
+                                     .build(None);
+        let context = unsafe {
+            display.create_context(&config, &ctx_attrs)
+                   .or_else(|_| display.create_context(&config, &fallback_ctx_attrs))?
+        };
+        let context = context.make_current_surfaceless()?;
+
+        Ok(Self { display: Display::Egl(display), _context: context })
+    }
+}
+
+fn write_png(path: &str, width: u32, height: u32, pixels: &[u8]) -> Result<(), Error> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width, height);
+
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    encoder.write_header()?
+           .write_image_data(pixels)?;
+
+    Ok(())
+}
+
+fn write_pngs(renderer: &mut dyn Renderer,
+              mos:      &[usize],
+              width:    u32,
+              height:   u32,
+              output:   &str) -> Result<(), Error> {
+    renderer.resize(width, height);
+
+    for &mo_idx in mos {
+        renderer.draw_mo(mo_idx);
+
+        let path = output.replace("{mo}", &mo_idx.to_string());
+
+        write_png(&path, width, height, &renderer.read_pixels())?;
+    }
+
+    Ok(())
+}
+
+// draws each mo and writes it out as a png, with any {mo} in the output path
+// replaced by the mo's index; without a gl context the cpu draws them instead
+#[cfg_attr(not(egl_backend), allow(unused_variables))]
+pub fn render(params:   &Params,
+              mos:      &[usize],
+              width:    u32,
+              height:   u32,
+              output:   &str,
+              cpu:      bool) -> Result<(), Error> {
+    let num_mos = params.mo_coefs.nrows();
+    let settings = &params.settings;
+
+    if width == 0 || height == 0 {
+        return Err("images need a nonzero width and height".into());
+    } else if settings.mode == RenderMode::Isosurface
+           && (!settings.iso.is_finite() || settings.iso <= 0.0) {
+        return Err("the isovalue must be positive".into());
+    } else if settings.mode == RenderMode::Volume
+           && (!settings.density.is_finite() || settings.density <= 0.0) {
+        return Err("the density must be positive".into());
+    } else if cpu && settings.filter == Filter::Cubic {
+        return Err("the cpu only filters volumes linearly, cubic needs a gl context".into());
+    } else if let Some(mo_idx) = mos.iter().find(|&&i| i >= num_mos) {
+        return Err(format!("no mo {mo_idx}, there are only {num_mos}").into());
+    }
+
+    let mos = if mos.is_empty() {
+        (0..num_mos).collect()
+    } else {
+        mos.to_vec()
+    };
+
+    if mos.len() > 1 && !output.contains("{mo}") {
+        return Err("the output path needs a {mo} in it to write more than one mo".into());
+    }
+
+    // the drawer and framebuffer have to go before the context they live in
+    #[cfg(egl_backend)]
+    if !cpu {
+        match Headless::new() {
+            Ok(headless) => {
+                let mut drawer = Drawer::new(&headless.display, params)?;
+                let framebuffer = Framebuffer::new(width, height)?;
+
+                framebuffer.bind();
+
+                return write_pngs(&mut drawer, &mos, width, height, output);
+            },
+            Err(err) => {
+                eprintln!("no gl context to draw with, drawing on the cpu: {err}");
+
+                if settings.filter == Filter::Cubic {
+                    eprintln!("the cpu filters volumes linearly, not cubically");
+                }
+            },
+        }
+    }
+
+    write_pngs(&mut CpuDrawer::new(params)?, &mos, width, height, output)
+}
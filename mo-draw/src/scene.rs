@@ -0,0 +1,202 @@
+use ndarray::{ Array1, Array2 };
+use serde::Deserialize;
+use crate::{
+    atom::{ self, Atom },
+    basis::{ AoOrdering, Basis, Primitive, Shell, ShellNorm },
+    drawer::Settings,
+    element,
+    error::Error,
+    mo_coefs::{ MoInfo, Spin },
+    Orbitals, Params,
+};
+
+// bumped whenever a document that used to load would now mean something else
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Units {
+    #[default]
+    Bohr,
+    Angstrom,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneAtom {
+    element:    String,
+    position:   [f64; 3],
+    #[serde(default)]
+    basis:      Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneShell {
+    l:              i32,
+    #[serde(default)]
+    cartesian:      bool,
+    exponents:      Vec<f64>,
+    coefficients:   Vec<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneBasis {
+    shells:         Vec<SceneShell>,
+    #[serde(default)]
+    normalization:  ShellNorm,
+}
+
+impl SceneBasis {
+    fn into_basis(self) -> Result<Basis, Error> {
+        let shells = self.shells.into_iter()
+                                .map(|s| Shell {
+                                    l:      s.l,
+                                    cart:   s.cartesian,
+                                    expns:  s.exponents,
+                                    coefs:  s.coefficients,
+                                })
+                                .collect::<Vec<_>>();
+
+        Basis::from_shells(&shells, self.normalization)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenePrimitive {
+    atom:       usize,
+    pows:       [u32; 3],
+    exponent:   f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneMo {
+    #[serde(default)]
+    energy:         Option<f64>,
+    #[serde(default)]
+    occupation:     Option<f64>,
+    #[serde(default)]
+    spin:           Spin,
+    coefficients:   Vec<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Scene {
+    version:    u32,
+    #[serde(default)]
+    units:      Units,
+    // of the mo coefficients within each shell
+    #[serde(default)]
+    ordering:   AoOrdering,
+    atoms:      Vec<SceneAtom>,
+    #[serde(default)]
+    bases:      Vec<SceneBasis>,
+    #[serde(default)]
+    primitives: Vec<ScenePrimitive>,
+    mos:        Vec<SceneMo>,
+    #[serde(default)]
+    render:     Settings,
+}
+
+impl Scene {
+    fn into_params(self) -> Result<Params, Error> {
+        if self.version != VERSION {
+            return Err(format!("unsupported scene version {}", self.version).into());
+        }
+
+        let scale = match self.units {
+            Units::Bohr => 1.0,
+            Units::Angstrom => atom::ANGSTROM,
+        };
+
+        let orbitals = match (self.bases.is_empty(), self.primitives.is_empty()) {
+            (false, true) => {
+                let bases = self.bases.into_iter()
+                                      .map(|basis| basis.into_basis())
+                                      .collect::<Result<Array1<_>, _>>()?;
+
+                Orbitals::Contracted(bases)
+            },
+            (true, false) => {
+                let prims = self.primitives.into_iter()
+                                           .map(|p| Primitive {
+                                               atom_idx:   p.atom,
+                                               pows:       p.pows,
+                                               expn:       p.exponent,
+                                           })
+                                           .collect::<Array1<_>>();
+
+                if prims.iter().any(|p| p.atom_idx >= self.atoms.len()) {
+                    return Err("scene primitive refers to a missing atom".into());
+                }
+
+                Orbitals::Primitive(prims)
+            },
+            _ => return Err("scene needs either bases or primitives".into()),
+        };
+
+        let mut atoms = Vec::with_capacity(self.atoms.len());
+
+        for a in self.atoms {
+            let atomic_num = element::atomic_num(&a.element)
+                                     .ok_or(format!("unknown element '{}'", a.element))?;
+            let basis_id = match (&orbitals, a.basis) {
+                (Orbitals::Contracted(bases), Some(i)) if i < bases.len() => i,
+                (Orbitals::Contracted(_), _) => {
+                    return Err("scene atom needs a valid basis".into());
+                },
+                _ => 0,
+            };
+
+            atoms.push(Atom { basis_id, atomic_num, position: a.position.map(|x| x * scale) });
+        }
+
+        let num_ao = match &orbitals {
+            Orbitals::Contracted(bases) => {
+                atoms.iter()
+                     .map(|a| bases[a.basis_id].num_aos())
+                     .sum()
+            },
+            Orbitals::Primitive(prims) => prims.len(),
+            Orbitals::Volume(_) => 0,
+        };
+
+        let mut mo_coefs = Array2::zeros((self.mos.len(), num_ao));
+        let mut mo_info = Vec::with_capacity(self.mos.len());
+
+        for (i, mo) in self.mos.into_iter().enumerate() {
+            if mo.coefficients.len() != num_ao {
+                return Err(format!("scene mo {i} has {} coefficients, expected {num_ao}",
+                                   mo.coefficients.len()).into());
+            }
+
+            mo_coefs.row_mut(i)
+                    .assign(&Array1::from(mo.coefficients));
+            mo_info.push(MoInfo { energy: mo.energy, occ: mo.occupation, spin: mo.spin });
+        }
+
+        let mut params = Params {
+            atoms:      Array1::from(atoms),
+            orbitals,
+            mo_coefs,
+            mo_info:    Array1::from(mo_info),
+            settings:   self.render,
+        };
+
+        params.reorder_aos(self.ordering)?;
+
+        Ok(params)
+    }
+}
+
+pub fn parse_json(text: &str) -> Result<Params, Error> {
+    serde_json::from_str::<Scene>(text)?.into_params()
+}
+
+pub fn parse_toml(text: &str) -> Result<Params, Error> {
+    toml::from_str::<Scene>(text)?.into_params()
+}
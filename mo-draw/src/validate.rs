@@ -0,0 +1,341 @@
+use std::{
+    f64::consts::PI,
+    fmt,
+};
+use ndarray::{ Array1, Array2, Axis };
+use crate::{
+    atom::Atom,
+    basis::{ Basis, Primitive },
+    cube::Volume,
+    eval::{ Ao, Evaluator },
+    harmonics,
+    Orbitals, Params,
+};
+
+// how far a norm may stray from one before it's worth a warning
+const NORM_TOL: f64 = 1e-2;
+// volumes are only summed over their grid, so they get more slack
+const VOLUME_NORM_TOL: f64 = 5e-2;
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub errors:     Vec<String>,
+    pub warnings:   Vec<String>,
+    pub notes:      Vec<String>,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for error in &self.errors {
+            writeln!(f, "error: {error}")?;
+        }
+
+        for warning in &self.warnings {
+            writeln!(f, "warning: {warning}")?;
+        }
+
+        for note in &self.notes {
+            writeln!(f, "note: {note}")?;
+        }
+
+        writeln!(f, "{} errors, {} warnings", self.errors.len(), self.warnings.len())
+    }
+}
+
+fn is_off(norm: f64, tol: f64) -> bool {
+    norm.is_nan() || (norm - 1.0).abs() > tol
+}
+
+fn check_atoms(report: &mut Report, atoms: &Array1<Atom>) {
+    for (i, atom) in atoms.iter().enumerate() {
+        if atom.position.iter().any(|x| !x.is_finite()) {
+            report.errors.push(format!("atom {i} has a position that isn't finite"));
+        }
+
+        for (j, other) in atoms.iter().enumerate().take(i) {
+            let r2 = (0..3).map(|k| (atom.position[k] - other.position[k]).powi(2))
+                           .sum::<f64>();
+
+            if r2 < 1e-6 {
+                report.warnings.push(format!("atoms {j} and {i} sit on top of each other"));
+            }
+        }
+    }
+}
+
+fn check_mos(report: &mut Report, params: &Params) {
+    let num_aos = params.num_aos();
+
+    if params.mo_coefs.ncols() != num_aos {
+        report.errors.push(format!("mos have {} coefficients, but there are {num_aos} aos",
+                                   params.mo_coefs.ncols()));
+    }
+
+    if params.mo_info.len() != params.mo_coefs.nrows() {
+        report.errors.push(format!("{} mos, but {} sets of mo info",
+                                   params.mo_coefs.nrows(), params.mo_info.len()));
+    }
+
+    for (i, row) in params.mo_coefs.rows().into_iter().enumerate() {
+        if row.iter().any(|c| !c.is_finite()) {
+            report.errors.push(format!("mo {i} has coefficients that aren't finite"));
+        }
+    }
+
+    for (i, info) in params.mo_info.iter().enumerate() {
+        if info.energy.is_some_and(|e| !e.is_finite()) {
+            report.errors.push(format!("mo {i} has an energy that isn't finite"));
+        }
+
+        if info.occ.is_some_and(|o| !(0.0..=2.0).contains(&o)) {
+            report.warnings.push(format!("mo {i} has an occupation outside [0, 2]"));
+        }
+    }
+}
+
+fn check_bases(report: &mut Report, params: &Params, bases: &Array1<Basis>) {
+    let mut valid = true;
+
+    for (i, atom) in params.atoms.iter().enumerate() {
+        if atom.basis_id >= bases.len() {
+            report.errors.push(format!("atom {i} refers to missing basis {}", atom.basis_id));
+            valid = false;
+        }
+    }
+
+    for (i, basis) in bases.iter().enumerate() {
+        if basis.shells.iter().flat_map(|s| &s.expns).any(|&a| !(a > 0.0 && a.is_finite())) {
+            report.errors.push(format!("basis {i} has exponents that aren't positive"));
+            valid = false;
+            continue;
+        }
+
+        for (label, shell) in basis.shell_labels().iter().zip(&basis.shells) {
+            let norm = shell.contraction_norm();
+
+            if is_off(norm, NORM_TOL) {
+                report.warnings.push(format!("basis {i} shell {label} is normalized to {norm:.6}"));
+            }
+        }
+    }
+
+    if valid {
+        check_mo_norms(report, params);
+    }
+}
+
+// one axis of the overlap between two cartesian gaussians, by expanding both
+// polynomials around the product center
+fn overlap_1d(i: u32, j: u32, a: f64, b: f64, xa: f64, xb: f64) -> f64 {
+    let p = a + b;
+    let xp = (a * xa + b * xb) / p;
+    let binomial = |n: u32, k: u32| (0..k).map(|m| (n - m) as f64 / (m + 1) as f64)
+                                          .product::<f64>();
+    let mut sum = 0.0;
+
+    for ki in 0..=i {
+        for kj in 0..=j {
+            let k = ki + kj;
+
+            if k % 2 == 1 {
+                continue;
+            }
+
+            let moment = harmonics::double_factorial(k as i32 - 1) / (2.0 * p).powi(k as i32 / 2)
+                       * (PI / p).sqrt();
+
+            sum += binomial(i, ki) * binomial(j, kj)
+                 * (xp - xa).powi((i - ki) as i32)
+                 * (xp - xb).powi((j - kj) as i32)
+                 * moment;
+        }
+    }
+
+    (-a * b / p * (xa - xb).powi(2)).exp() * sum
+}
+
+// every ao is a sum of cartesian gaussians, one for each term of its angular
+// function and each of its primitives, so the overlap sums over both pairs
+fn ao_overlap(a: &Ao, b: &Ao) -> f64 {
+    let mut overlap = 0.0;
+
+    for ta in &a.ang {
+        for tb in &b.ang {
+            for (&ea, ca) in a.expns.iter().zip(&a.coefs) {
+                for (&eb, cb) in b.expns.iter().zip(&b.coefs) {
+                    overlap += ta.coef * tb.coef * ca * cb
+                             * (0..3).map(|k| overlap_1d(ta.pows[k], tb.pows[k], ea, eb,
+                                                         a.org[k], b.org[k]))
+                                     .product::<f64>();
+                }
+            }
+        }
+    }
+
+    overlap
+}
+
+fn ao_overlaps(aos: &[Ao]) -> Array2<f64> {
+    let mut overlaps = Array2::zeros((aos.len(), aos.len()));
+
+    for i in 0..aos.len() {
+        for j in 0..=i {
+            let overlap = ao_overlap(&aos[i], &aos[j]);
+
+            overlaps[[i, j]] = overlap;
+            overlaps[[j, i]] = overlap;
+        }
+    }
+
+    overlaps
+}
+
+// c S c^T for every mo; any mismatch in the aos is check_mos' to report
+fn check_mo_norms(report: &mut Report, params: &Params) {
+    let Ok(evaluator) = Evaluator::new(params) else {
+        return;
+    };
+
+    if params.mo_coefs.ncols() != evaluator.aos.len() {
+        return;
+    }
+
+    let overlaps = ao_overlaps(&evaluator.aos);
+    let norms = (params.mo_coefs.dot(&overlaps) * &params.mo_coefs).sum_axis(Axis(1));
+
+    for (i, &norm) in norms.iter().enumerate() {
+        if is_off(norm, NORM_TOL) {
+            report.warnings.push(format!("mo {i} is normalized to {norm:.6}"));
+        }
+    }
+}
+
+fn check_primitives(report: &mut Report, params: &Params, prims: &Array1<Primitive>) {
+    let mut valid = true;
+
+    for (i, prim) in prims.iter().enumerate() {
+        if prim.atom_idx >= params.atoms.len() {
+            report.errors.push(format!("primitive {i} refers to missing atom {}", prim.atom_idx));
+            valid = false;
+        }
+
+        if !(prim.expn > 0.0 && prim.expn.is_finite()) {
+            report.errors.push(format!("primitive {i} has an exponent that isn't positive"));
+            valid = false;
+        }
+    }
+
+    if valid {
+        check_mo_norms(report, params);
+    }
+}
+
+fn check_volume(report: &mut Report, volume: &Volume) {
+    let [a, b, c] = volume.grid.axes;
+    let voxel = (a[0] * (b[1] * c[2] - b[2] * c[1])
+               - a[1] * (b[0] * c[2] - b[2] * c[0])
+               + a[2] * (b[0] * c[1] - b[1] * c[0])).abs();
+
+    if voxel == 0.0 {
+        report.errors.push("volume grid axes are degenerate".into());
+        return;
+    }
+
+    for (i, values) in volume.values.outer_iter().enumerate() {
+        if values.iter().any(|v| !v.is_finite()) {
+            report.errors.push(format!("volume {i} has values that aren't finite"));
+            continue;
+        }
+
+        let norm = values.iter()
+                         .map(|v| v * v)
+                         .sum::<f64>() * voxel;
+
+        if is_off(norm, VOLUME_NORM_TOL) {
+            report.warnings.push(format!("volume {i} is normalized to {norm:.6} over its grid"));
+        }
+    }
+}
+
+pub fn check(params: &Params) -> Report {
+    let mut report = Report::default();
+
+    check_atoms(&mut report, &params.atoms);
+
+    match &params.orbitals {
+        Orbitals::Contracted(bases) => check_bases(&mut report, params, bases),
+        Orbitals::Primitive(prims) => check_primitives(&mut report, params, prims),
+        Orbitals::Volume(volume) => check_volume(&mut report, volume),
+    }
+
+    check_mos(&mut report, params);
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+    use crate::{
+        basis::Shell,
+        drawer::Settings,
+        mo_coefs::MoInfo,
+    };
+    use super::*;
+
+    fn s_shell(expn: f64) -> Shell {
+        Shell { l: 0, cart: false, expns: vec![expn], coefs: vec![1.0] }
+    }
+
+    fn params(atoms: Array1<Atom>, bases: Array1<Basis>, mo_coefs: Array2<f64>) -> Params {
+        Params {
+            atoms,
+            orbitals:   Orbitals::Contracted(bases),
+            mo_info:    (0..mo_coefs.nrows()).map(|_| MoInfo::default()).collect(),
+            mo_coefs,
+            settings:   Settings::default(),
+        }
+    }
+
+    fn atom(basis_id: usize, position: [f64; 3]) -> Atom {
+        Atom { basis_id, atomic_num: 1, position }
+    }
+
+    #[test]
+    fn each_ao_alone_is_normalized() {
+        let mut d = Shell { l: 2, cart: false, expns: vec![1.2, 0.4], coefs: vec![0.6, 0.5] };
+        let norm = d.contraction_norm().sqrt();
+
+        d.coefs.iter_mut().for_each(|c| *c /= norm);
+
+        let shells = vec![
+            s_shell(0.8),
+            Shell { l: 1, cart: false, expns: vec![0.7], coefs: vec![1.0] },
+            d,
+            Shell { l: 3, cart: false, expns: vec![0.6], coefs: vec![1.0] },
+            Shell { l: 2, cart: true, expns: vec![0.9], coefs: vec![1.0] },
+        ];
+        let num_aos = 1 + 3 + 5 + 7 + 6;
+        let params = params(array![atom(0, [0.3, -0.2, 0.1])],
+                            array![Basis { shells }],
+                            Array2::eye(num_aos));
+        let report = check(&params);
+
+        assert!(report.errors.is_empty() && report.warnings.is_empty(), "{report}");
+    }
+
+    #[test]
+    fn mos_across_atoms_count_the_overlap() {
+        let (expn, dist): (f64, f64) = (0.5, 1.4);
+        let overlap = (-expn * dist * dist / 2.0).exp();
+        let bonding = 1.0 / (2.0 + 2.0 * overlap).sqrt();
+        let naive = 1.0 / 2f64.sqrt();
+        let params = params(array![atom(0, [0.0; 3]), atom(0, [0.0, 0.0, dist])],
+                            array![Basis { shells: vec![s_shell(expn)] }],
+                            array![[bonding, bonding], [naive, naive]]);
+        let report = check(&params);
+
+        assert_eq!(report.warnings.len(), 1, "{report}");
+        assert!(report.warnings[0].starts_with("mo 1 "), "{report}");
+    }
+}
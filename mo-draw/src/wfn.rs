@@ -0,0 +1,495 @@
+use std::collections::HashMap;
+use ndarray::{ Array1, Array2 };
+use crate::{
+    atom::Atom,
+    basis::Primitive,
+    error::Error,
+    mo_coefs::{ MoInfo, Spin },
+    Orbitals, Params,
+};
+
+// cartesian powers for each aim type assignment, following multiwfn
+const TYPE_POWS: [[u32; 3]; 35] = [
+    [0, 0, 0],
+    [1, 0, 0], [0, 1, 0], [0, 0, 1],
+    [2, 0, 0], [0, 2, 0], [0, 0, 2], [1, 1, 0], [1, 0, 1], [0, 1, 1],
+    [3, 0, 0], [0, 3, 0], [0, 0, 3], [2, 1, 0], [2, 0, 1],
+    [0, 2, 1], [1, 2, 0], [1, 0, 2], [0, 1, 2], [1, 1, 1],
+    [0, 0, 4], [0, 1, 3], [0, 2, 2], [0, 3, 1], [0, 4, 0],
+    [1, 0, 3], [1, 1, 2], [1, 2, 1], [1, 3, 0], [2, 0, 2],
+    [2, 1, 1], [2, 2, 0], [3, 0, 1], [3, 1, 0], [4, 0, 0],
+];
+
+fn parse_f64(s: &str) -> Result<f64, Error> {
+    Ok(s.replace(['D', 'd'], "E")
+        .parse()?)
+}
+
+// everything both formats boil down to, before checking it for consistency
+struct Wavefunction {
+    atoms:      Vec<(u32, [f64; 3])>,
+    centers:    Vec<usize>,
+    types:      Vec<usize>,
+    expns:      Vec<f64>,
+    mos:        Vec<(MoInfo, Vec<f64>)>,
+}
+
+impl Wavefunction {
+    fn into_params(self) -> Result<Params, Error> {
+        let num_prims = self.expns.len();
+
+        if self.centers.len() != num_prims || self.types.len() != num_prims {
+            return Err("inconsistent primitive assignments".into());
+        }
+
+        let mut prims = Vec::with_capacity(num_prims);
+
+        for i in 0..num_prims {
+            let atom_idx = self.centers[i];
+            let ty = self.types[i];
+
+            if atom_idx < 1 || atom_idx > self.atoms.len() {
+                return Err("invalid primitive center".into());
+            } else if ty < 1 || ty > TYPE_POWS.len() {
+                return Err(format!("unsupported primitive type {ty}").into());
+            }
+
+            prims.push(Primitive {
+                atom_idx:   atom_idx - 1,
+                pows:       TYPE_POWS[ty - 1],
+                expn:       self.expns[i],
+            });
+        }
+
+        let mut mo_coefs = Array2::zeros((self.mos.len(), num_prims));
+
+        for (i, (_, coefs)) in self.mos.iter().enumerate() {
+            if coefs.len() != num_prims {
+                return Err("mo coefficients do not match the primitives".into());
+            }
+
+            for (j, &coef) in coefs.iter().enumerate() {
+                mo_coefs[(i, j)] = coef;
+            }
+        }
+
+        let atoms = self.atoms.into_iter()
+                              .map(|(atomic_num, position)| {
+                                  Atom { basis_id: 0, atomic_num, position }
+                              })
+                              .collect();
+        let mo_info = self.mos.into_iter()
+                              .map(|(info, _)| info)
+                              .collect();
+
+        Ok(Params {
+            atoms,
+            orbitals:   Orbitals::Primitive(Array1::from(prims)),
+            mo_coefs,
+            mo_info,
+            settings:   Default::default(),
+        })
+    }
+}
+
+fn value_after<'a>(line: &'a str, key: &str) -> Result<&'a str, Error> {
+    let (_, rest) = line.split_once(key)
+                        .ok_or(format!("wfn line is missing '{key}'"))?;
+
+    Ok(rest.split_whitespace()
+           .next()
+           .ok_or(format!("wfn line has no value after '{key}'"))?)
+}
+
+// assignments are fixed width, so wide indices can run into each other
+fn parse_assignments(rest: &str, into: &mut Vec<usize>) -> Result<(), Error> {
+    let bytes = rest.as_bytes();
+
+    for chunk in bytes.chunks(3) {
+        let s = std::str::from_utf8(chunk)?.trim();
+
+        if !s.is_empty() {
+            into.push(s.parse()?);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn parse_wfn(text: &str) -> Result<Params, Error> {
+    let mut lines = text.lines()
+                        .skip(1);
+
+    let header = lines.next()
+                      .ok_or("wfn file has no header")?;
+    // the nucleus count comes right after the primitive count
+    let num_atoms: usize = value_after(header, "PRIMITIVES")?.parse()?;
+
+    let mut wfn = Wavefunction {
+        atoms:      Vec::with_capacity(num_atoms),
+        centers:    Vec::new(),
+        types:      Vec::new(),
+        expns:      Vec::new(),
+        mos:        Vec::new(),
+    };
+
+    for _ in 0..num_atoms {
+        let line = lines.next()
+                        .ok_or("truncated wfn nuclei")?;
+        let (_, rest) = line.split_once(')')
+                            .ok_or("invalid wfn nucleus line")?;
+        let fields = rest.split_whitespace()
+                         .collect::<Vec<_>>();
+
+        if fields.len() < 6 {
+            return Err("invalid wfn nucleus line".into());
+        }
+
+        let position = [parse_f64(fields[0])?, parse_f64(fields[1])?, parse_f64(fields[2])?];
+        let charge = parse_f64(value_after(rest, "=")?)?;
+
+        wfn.atoms.push((charge.round() as u32, position));
+    }
+
+    while let Some(line) = lines.next() {
+        if let Some(rest) = line.strip_prefix("CENTRE ASSIGNMENTS") {
+            parse_assignments(rest.get(2..).unwrap_or(""), &mut wfn.centers)?;
+        } else if let Some(rest) = line.strip_prefix("TYPE ASSIGNMENTS") {
+            parse_assignments(rest.get(4..).unwrap_or(""), &mut wfn.types)?;
+        } else if let Some(rest) = line.strip_prefix("EXPONENTS") {
+            for s in rest.split_whitespace() {
+                wfn.expns.push(parse_f64(s)?);
+            }
+        } else if line.starts_with("MO") {
+            let info = MoInfo {
+                energy: Some(parse_f64(value_after(line, "ENERGY =")?)?),
+                occ:    Some(parse_f64(value_after(line, "OCC NO =")?)?),
+                // wfn files don't label spin, unrestricted ones just list
+                // the beta orbitals after the alpha ones
+                spin:   Spin::Alpha,
+            };
+            let mut coefs = Vec::with_capacity(wfn.expns.len());
+
+            while coefs.len() < wfn.expns.len() {
+                let line = lines.next()
+                                .ok_or("truncated wfn mo coefficients")?;
+
+                for s in line.split_whitespace() {
+                    coefs.push(parse_f64(s)?);
+                }
+            }
+
+            wfn.mos.push((info, coefs));
+        } else if line.starts_with("END DATA") {
+            break;
+        }
+    }
+
+    wfn.into_params()
+}
+
+// wfx is xml-ish: every value sits between an opening and closing tag line
+fn split_tags(text: &str) -> HashMap<String, Vec<&str>> {
+    let mut tags = HashMap::new();
+    let mut open: Option<(String, Vec<&str>)> = None;
+
+    for line in text.lines() {
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        match open.take() {
+            Some((name, contents)) if line == format!("</{name}>") => {
+                tags.insert(name, contents);
+            },
+            Some((name, mut contents)) => {
+                contents.push(line);
+                open = Some((name, contents));
+            },
+            None => if let Some(name) = line.strip_prefix('<')
+                                            .and_then(|s| s.strip_suffix('>')) {
+                open = Some((name.to_string(), Vec::new()));
+            },
+        }
+    }
+
+    tags
+}
+
+pub fn parse_wfx(text: &str) -> Result<Params, Error> {
+    let tags = split_tags(text);
+    let get = |name: &str| {
+        tags.get(name)
+            .ok_or(format!("wfx file has no <{name}> section"))
+    };
+    let values = |name: &str| -> Result<Vec<&str>, Error> {
+        Ok(get(name)?.iter()
+                     .flat_map(|line| line.split_whitespace())
+                     .collect())
+    };
+    let reals = |name: &str| -> Result<Vec<f64>, Error> {
+        values(name)?.into_iter()
+                     .map(parse_f64)
+                     .collect()
+    };
+    let ints = |name: &str| -> Result<Vec<usize>, Error> {
+        Ok(values(name)?.into_iter()
+                        .map(|s| s.parse())
+                        .collect::<Result<_, _>>()?)
+    };
+
+    let atomic_nums = ints("Atomic Numbers")?;
+    let coords = reals("Nuclear Cartesian Coordinates")?;
+
+    if coords.len() != 3 * atomic_nums.len() {
+        return Err("wfx coordinates do not match the atoms".into());
+    }
+
+    let atoms = atomic_nums.iter()
+                           .enumerate()
+                           .map(|(i, &z)| (z as u32, [coords[3 * i], coords[3 * i + 1], coords[3 * i + 2]]))
+                           .collect();
+
+    let occs = reals("Molecular Orbital Occupation Numbers")?;
+    let energies = reals("Molecular Orbital Energies")?;
+    let spins = get("Molecular Orbital Spin Types")?;
+
+    if energies.len() != occs.len() || spins.len() != occs.len() {
+        return Err("inconsistent wfx orbital sections".into());
+    }
+
+    // the coefficient section nests an <MO Number> tag ahead of each mo
+    let mut coefses: Vec<Vec<f64>> = Vec::new();
+    let mut lines = get("Molecular Orbital Primitive Coefficients")?.iter();
+
+    while let Some(line) = lines.next() {
+        if *line == "<MO Number>" {
+            lines.next();
+            lines.next();
+            coefses.push(Vec::new());
+        } else {
+            let coefs = coefses.last_mut()
+                               .ok_or("wfx coefficients before mo number")?;
+
+            for s in line.split_whitespace() {
+                coefs.push(parse_f64(s)?);
+            }
+        }
+    }
+
+    if coefses.len() != occs.len() {
+        return Err("wfx coefficients do not match the orbitals".into());
+    }
+
+    let mut mos = Vec::with_capacity(occs.len());
+
+    for (i, coefs) in coefses.into_iter().enumerate() {
+        let spin = match spins[i] {
+            "Alpha" | "Alpha and Beta" => Spin::Alpha,
+            "Beta" => Spin::Beta,
+            _ => return Err("invalid wfx mo spin type".into()),
+        };
+        let info = MoInfo { energy: Some(energies[i]), occ: Some(occs[i]), spin };
+
+        mos.push((info, coefs));
+    }
+
+    let wfn = Wavefunction {
+        atoms,
+        centers:    ints("Primitive Centers")?,
+        types:      ints("Primitive Types")?,
+        expns:      reals("Primitive Exponents")?,
+        mos,
+    };
+
+    wfn.into_params()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write;
+    use crate::harmonics;
+    use super::*;
+
+    const WFN: &str = "\
+water fragment
+GAUSSIAN              2 MOL ORBITALS      4 PRIMITIVES        2 NUCLEI
+  O    1    (CENTRE  1)   0.00000000  0.00000000  0.22143053  CHARGE =  8.0
+  H    2    (CENTRE  2)   0.00000000  1.43042809 -0.88572213  CHARGE =  1.0
+CENTRE ASSIGNMENTS    1  1  1  2
+TYPE ASSIGNMENTS      1  2  9  1
+EXPONENTS  0.1307093D+03 0.5033151D+01 0.1169596D+01 0.3425251D+01
+MO    1                     OCC NO =    2.00000000  ORB. ENERGY =  -20.25157700
+  0.99421000D+00  0.25000000D-01 -0.12500000D+00  0.10000000D+01
+MO    2                     OCC NO =    0.00000000  ORB. ENERGY =    0.60463300
+ -0.23376000D+00  0.10000000D+01  0.00000000D+00 -0.50000000D+00
+END DATA
+ THE SCF ENERGY =    -74.962929947825 THE VIRIAL(-V/T)=   2.00654530
+";
+
+    const WFX: &str = "\
+<Atomic Numbers>
+8
+1
+</Atomic Numbers>
+<Nuclear Cartesian Coordinates>
+0.0 0.0 0.22143053
+0.0 1.43042809 -0.88572213
+</Nuclear Cartesian Coordinates>
+<Primitive Centers>
+1 1 1 2
+</Primitive Centers>
+<Primitive Types>
+1 2 9 1
+</Primitive Types>
+<Primitive Exponents>
+1.307093E+02 5.033151E+00
+1.169596E+00 3.425251E+00
+</Primitive Exponents>
+<Molecular Orbital Occupation Numbers>
+1.0 1.0
+</Molecular Orbital Occupation Numbers>
+<Molecular Orbital Energies>
+-20.251577 -20.1
+</Molecular Orbital Energies>
+<Molecular Orbital Spin Types>
+Alpha
+Beta
+</Molecular Orbital Spin Types>
+<Molecular Orbital Primitive Coefficients>
+<MO Number>
+1
+</MO Number>
+9.9421E-01 2.5E-02
+-1.25E-01 1.0
+<MO Number>
+2
+</MO Number>
+-2.3376E-01 1.0 0.0 -5.0E-01
+</Molecular Orbital Primitive Coefficients>
+";
+
+    fn prims(params: &Params) -> &Array1<Primitive> {
+        match &params.orbitals {
+            Orbitals::Primitive(prims) => prims,
+            _ => panic!("wfn input gave contracted orbitals"),
+        }
+    }
+
+    // both fixtures hold the same wavefunction
+    fn assert_water(params: &Params) {
+        assert_eq!(params.atoms.iter().map(|a| a.atomic_num).collect::<Vec<_>>(), [8, 1]);
+        assert_eq!(params.atoms[1].position, [0.0, 1.43042809, -0.88572213]);
+
+        let prims = prims(params);
+
+        assert_eq!(prims.iter().map(|p| p.atom_idx).collect::<Vec<_>>(), [0, 0, 0, 1]);
+        assert_eq!(prims.iter().map(|p| p.pows).collect::<Vec<_>>(),
+                   [[0, 0, 0], [1, 0, 0], [1, 0, 1], [0, 0, 0]]);
+        assert_eq!(prims[0].expn, 130.7093);
+        assert_eq!(params.mo_coefs.row(0).to_vec(), [0.99421, 0.025, -0.125, 1.0]);
+        assert_eq!(params.mo_coefs.row(1).to_vec(), [-0.23376, 1.0, 0.0, -0.5]);
+        assert_eq!(params.mo_info[0].energy, Some(-20.251577));
+    }
+
+    #[test]
+    fn reads_wfn() {
+        let params = parse_wfn(WFN).unwrap();
+
+        assert_water(&params);
+        assert_eq!(params.mo_info.iter().map(|i| i.occ).collect::<Vec<_>>(),
+                   [Some(2.0), Some(0.0)]);
+    }
+
+    #[test]
+    fn reads_wfx() {
+        let params = parse_wfx(WFX).unwrap();
+
+        assert_water(&params);
+        assert_eq!(params.mo_info.iter().map(|i| i.spin).collect::<Vec<_>>(),
+                   [Spin::Alpha, Spin::Beta]);
+    }
+
+    // past 99 atoms the centre indices fill their columns and run together
+    #[test]
+    fn reads_wide_wfn_assignments() {
+        let num_atoms = 120;
+        let idxs = (1..=num_atoms).collect::<Vec<usize>>();
+        let mut text = format!("chain\nGAUSSIAN              1 MOL ORBITALS    \
+                                {num_atoms:3} PRIMITIVES      {num_atoms:3} NUCLEI\n");
+
+        for &i in &idxs {
+            writeln!(text, "  H  {i:3}    (CENTRE{i:3})   0.00000000  0.00000000 {:11.8}  \
+                            CHARGE =  1.0", 1.4 * i as f64).unwrap();
+        }
+
+        for chunk in idxs.chunks(20) {
+            let centres = chunk.iter().map(|i| format!("{i:3}")).collect::<String>();
+            let types = chunk.iter().map(|i| format!("{:3}", i % 4 + 1)).collect::<String>();
+
+            writeln!(text, "CENTRE ASSIGNMENTS  {centres}").unwrap();
+            writeln!(text, "TYPE ASSIGNMENTS    {types}").unwrap();
+        }
+
+        assert!(text.contains("101102103"));
+
+        for chunk in idxs.chunks(5) {
+            let expns = chunk.iter().map(|&i| format!("{:14.7E}", 0.01 * i as f64));
+
+            writeln!(text, "EXPONENTS {}", expns.collect::<String>()).unwrap();
+        }
+
+        text.push_str("MO    1                     OCC NO =    2.00000000  ORB. ENERGY =   -0.50000000\n");
+
+        for chunk in idxs.chunks(5) {
+            let coefs = chunk.iter().map(|&i| format!("{:16.8E}", -0.001 * i as f64));
+
+            writeln!(text, "{}", coefs.collect::<String>()).unwrap();
+        }
+
+        text.push_str("END DATA\n");
+
+        let params = parse_wfn(&text).unwrap();
+        let prims = prims(&params);
+
+        assert_eq!(params.atoms.len(), num_atoms);
+        assert_eq!(prims.len(), num_atoms);
+
+        for (i, prim) in prims.iter().enumerate() {
+            assert_eq!(prim.atom_idx, i);
+            assert_eq!(prim.pows, TYPE_POWS[(i + 1) % 4]);
+            assert!((prim.expn - 0.01 * (i + 1) as f64).abs() < 1e-12);
+            assert!((params.mo_coefs[(0, i)] + 0.001 * (i + 1) as f64).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn type_pows_follow_multiwfn() {
+        // each l's types are every one of its cartesian components
+        let mut start = 0;
+
+        for l in 0..=harmonics::MAX_L {
+            let num = harmonics::num_cart(l);
+            let mut pows = TYPE_POWS[start..start + num].to_vec();
+            let mut cart = harmonics::cart_pows(l);
+
+            pows.sort();
+            cart.sort();
+            assert_eq!(pows, cart, "l = {l}");
+            start += num;
+        }
+
+        assert_eq!(start, TYPE_POWS.len());
+
+        // and where multiwfn's order parts from the usual one: d ends xy xz
+        // yz, f's mixed terms start at xxy, and g runs from zzzz to xxxx
+        assert_eq!(TYPE_POWS[4..10],
+                   [[2, 0, 0], [0, 2, 0], [0, 0, 2], [1, 1, 0], [1, 0, 1], [0, 1, 1]]);
+        assert_eq!(TYPE_POWS[13], [2, 1, 0]);
+        assert_eq!(TYPE_POWS[19], [1, 1, 1]);
+        assert_eq!(TYPE_POWS[20], [0, 0, 4]);
+        assert_eq!(TYPE_POWS[34], [4, 0, 0]);
+    }
+}